@@ -0,0 +1,104 @@
+use serialport::{DataBits, Parity, StopBits};
+
+use crate::os3000::{self, channels::Channel, OscilloscopeCapture};
+
+// exit codes of the headless capture
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_CAPTURE_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_WRITE_FAILED: i32 = 3;
+
+const USAGE: &str = "usage: scope-ui --capture --port <port> [--channel 1-4] [--baud <baudrate>] [--retries <n>] --out <file.csv>";
+
+struct HeadlessArgs {
+    port: String,
+    channel: Channel,
+    baudrate: u32,
+    retries: u32,
+    out: String
+}
+
+#[doc = "Returns true if the GUI should be skipped"]
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--capture")
+}
+
+#[doc = "Reads the headless options, everything that isn't known is an error"]
+fn parse_args(args: &[String]) -> Result<HeadlessArgs, String> {
+    let mut port: Option<String> = None;
+    let mut out: Option<String> = None;
+    let mut channel = Channel::DISPLAY1;
+    let mut baudrate: u32 = 9600;
+    let mut retries: u32 = 10;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--capture" {
+            continue;
+        }
+        let value = iter.next().ok_or(format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "--port" => port = Some(value.clone()),
+            "--out" => out = Some(value.clone()),
+            "--channel" => channel = value.parse::<u8>().ok().and_then(Channel::from_number).ok_or(format!("invalid channel {value}"))?,
+            "--baud" => baudrate = value.parse().map_err(|_| format!("invalid baudrate {value}"))?,
+            "--retries" => retries = value.parse().map_err(|_| format!("invalid retry count {value}"))?,
+            _ => return Err(format!("unknown option {arg}"))
+        }
+    }
+
+    Ok(HeadlessArgs {
+        port: port.ok_or("--port is required")?,
+        out: out.ok_or("--out is required")?,
+        channel,
+        baudrate,
+        retries
+    })
+}
+
+#[doc = "Captures one waveform without the GUI, writes it as CSV and returns the exit code"]
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut capture = match OscilloscopeCapture::open(&args.port, args.baudrate, StopBits::One, DataBits::Eight, Parity::None) {
+        Ok(capture) => capture,
+        Err(e) => {
+            eprintln!("Failed to open {}: {e}", args.port);
+            return EXIT_CAPTURE_FAILED;
+        }
+    };
+
+    // the link is unreliable at higher baudrates, so retry like the GUI does
+    let mut attempt = 0;
+    let (waveform, time_per_div, voltage_per_div) = loop {
+        attempt += 1;
+        match capture.get_waveform_data(args.channel, 1.0) {
+            Ok(data) => break data,
+            Err(e) => {
+                eprintln!("Capture attempt {attempt} failed: {e}");
+                if attempt > args.retries {
+                    return EXIT_CAPTURE_FAILED;
+                }
+                capture.resync();
+            }
+        }
+    };
+
+    let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+    match std::fs::write(&args.out, os3000::csv::format_waveform(&waveform, seconds_per_div, &voltage_per_div.unit_name, &os3000::csv::CsvOptions::default(), 0)) {
+        Ok(_) => {
+            println!("Wrote {} samples to {}", waveform.len(), args.out);
+            EXIT_OK
+        },
+        Err(e) => {
+            eprintln!("Failed to write {}: {e}", args.out);
+            EXIT_WRITE_FAILED
+        }
+    }
+}
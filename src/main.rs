@@ -1,24 +1,59 @@
 use imgui::*;
+use imgui::draw_list::DrawListFlags;
 use mint::Vector4;
-use serialport::StopBits;
-use std::{sync::{mpsc, Arc, Mutex}, thread::{self, sleep}, time::Duration, vec};
+use serialport::{DataBits, Parity, StopBits};
+use std::{collections::VecDeque, sync::{mpsc, Arc, Mutex}, thread::{self, sleep}, time::{Duration, Instant}, vec};
 use std::sync::mpsc::{Sender,Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::panic::AssertUnwindSafe;
 
 mod support;
 mod os3000;
-use os3000::{channels::Channel, processing::{self, bezier2_interpolate_samples, bezier_interpolate_samples, catmull_rom_interpolate_samples, cosine_interpolate_samples, linear_interpolate_samples}, OscilloscopeCapture, ValueUnitPair};
+mod headless;
+use os3000::{channels::Channel, ChannelCapture, CommandDelays, processing::{self, bezier2_interpolate_samples, bezier_interpolate_samples, catmull_rom_interpolate_samples, cosine_interpolate_samples, linear_interpolate_samples}, OscilloscopeCapture, ValueUnitPair};
 use os3000::errors::OscilloscopeError;
+use os3000::diagnostics::DiagnosticsReport;
+use os3000::condition::CaptureConditions;
+use os3000::format::{self, CaptureRecord};
+use os3000::svg::{self, SvgTrace, SvgView};
+use os3000::csv::{CsvOptions, TimeColumn};
+use std::io::Write;
+use os3000::processing::{Calibration, IdealFit, IdealShape, Measurement, MeasurementSet, ReferenceLevels, SampleFormat, TriggerConfig, TriggerCoupling, TriggerSlope};
 #[derive(Clone)]
 struct CaptureConfig {
     do_capture: bool,
     open_port: bool,
     two_stopbits: bool,
+    data_bits: DataBits,
+    parity: Parity,
     do_s1: bool,
     do_ro: bool,
     do_ri: bool,
+    do_wi: bool,
+    do_diag: bool,
     port_name: String,
     channel: Channel,
+    wi_channel: Channel,
+    wi_data: Vec<u8>,
     baudrate: u32,
+    record_length: u32,
+    sample_format: SampleFormat,
+    ri_read_attempts: u32,
+    // None detects the Ri header length from the frames
+    ri_header_length: Option<usize>,
+    delays: CommandDelays,
+    // failed captures in a row before giving up, 0 retries forever
+    max_retries: u32,
+    queue_depth: usize,
+    capture_period: Option<Duration>,
+    // the other channels polled in the same acquisition, by Channel::index, the selected channel is always polled
+    acquire_channels: [bool; 4],
+    // points of a record read in several Ri blocks, None reads a single record
+    deep_record: Option<u32>,
+    // per channel, applied to every capture before anything else sees it
+    calibration: [Calibration; 4],
+    // single wild points further than this many sigma from both neighbors are interpolated over, None leaves them
+    despike_sigma: Option<f64>,
 }
 
 #[allow(dead_code)]
@@ -35,47 +70,923 @@ enum ScopeStatus {
     RiFail,
     RiRetry,
     RiSuccess,
+    // finished blocks and all blocks of a deep record
+    RiBlock(usize, usize),
+    EmptyChannel,
+    Wi,
+    WiFail,
+    WiSuccess,
+    Diag,
+    DiagFail,
+    DiagSuccess,
+    Resyncing,
+    NoResponse,
+    RetriesExhausted,
+    // the capture thread panicked, with the panic message
+    InternalError(String),
     UnknownError
 }
 
+#[doc = "The message of a caught panic, panics with something other than a string in them don't have one"]
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    panic.downcast_ref::<String>().cloned().unwrap_or(String::from("unknown panic"))
+}
+
+#[doc = "Vertical placement of a channel's traces, applied only when drawing"]
+#[derive(Clone, Copy)]
+struct ChannelDisplay {
+    y_offset: f32,
+    y_scale: f32
+}
+
+impl Default for ChannelDisplay {
+    fn default() -> Self {
+        ChannelDisplay {y_offset: 0.0, y_scale: 1.0}
+    }
+}
+
+#[doc = "How a trace is drawn, offsets and scales are in pixels, the x scale per drawn point and the y scale per unit of the samples"]
+#[derive(Clone, Copy)]
+struct TraceStyle {
+    color: ImColor32,
+    offsets: (f32, f32),
+    scales: (f32, f32),
+    thickness: f32
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SnapMode {
+    None,
+    Trace,
+    Grid
+}
+
 struct ScopeResponse {
     s1_result: bool,
     capture_conditions: String,
     waveform_data: Vec<f64>,
+    raw_data: Vec<u8>,
     time_per_div: ValueUnitPair,
-    voltage_per_div: ValueUnitPair
+    voltage_per_div: ValueUnitPair,
+    diagnostics: Option<DiagnosticsReport>,
+    // the other channels of a multi channel capture
+    extra_traces: Vec<ChannelCapture>
 }
 
+// warn about clipping once this fraction of the samples sits on the rails
+const CLIP_WARNING_FRACTION: f32 = 0.01;
+
+// number of captures shown in the measurement sparklines
+const MEASUREMENT_HISTORY_LENGTH: usize = 64;
+// the two control columns share this much of the display each, but don't get narrower than the minimum
+const CONTROL_COLUMN_FRACTION: f32 = 0.27;
+const CONTROL_COLUMN_MIN_WIDTH: f32 = 200.0;
+
+// number of frames shown in the frame time graph
+const FRAME_TIME_HISTORY_LENGTH: usize = 300;
+// number of recent frames the sample cap looks at, and how often it's adjusted
+const SAMPLE_CAP_FRAMES: usize = 30;
+const SAMPLE_CAP_INTERVAL: Duration = Duration::from_secs(1);
+// lowest fraction of the interpolation samples the cap goes down to
+const SAMPLE_CAP_MIN: f32 = 0.05;
+
+// upper limit for the number of captures waiting for the UI
+const MAX_QUEUE_DEPTH: usize = 16;
+
+// limits for the display controls, typed in values are clamped to these as well
+const SCALE_RANGE: (f32, f32) = (0.1, 10.0);
+const THICKNESS_RANGE: (f32, f32) = (1.0, 5.0);
+const INTERPOL_SAMPLES_RANGE: (usize, usize) = (1001, 16000);
+const INTERPOL2_SAMPLES_RANGE: (usize, usize) = (100, u16::MAX as usize);
+
+// commands in a row without a single byte back before the capture gives up on the scope
+const NO_RESPONSE_LIMIT: u32 = 3;
+// how long the capture thread waits before it starts over after a panic
+const CAPTURE_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+// the interpolators need at least this many samples, shorter waveforms are replaced by a flat line
+const MIN_WAVEFORM_SAMPLES: usize = 4;
+
+const AFTERGLOW_CURVES: [&str; 2] = ["Linear", "Exponential"];
+
+// captures kept in the spectrogram, one row each
+const SPECTROGRAM_ROWS: usize = 100;
+// bins are merged down to at most this many columns, the drawlist can't take a rectangle per bin and row
+const SPECTROGRAM_COLUMNS: usize = 256;
+
+// size of one heatmap cell in pixels, single pixels would be too many rectangles for the drawlist
+const HEATMAP_CELL: f32 = 2.0;
+
+// the drawing window grid has this many divisions
+const HORIZONTAL_DIVISIONS: f32 = 10.0;
+const VERTICAL_DIVISIONS: f32 = 8.0;
+// the center lines of the grid are this many times as thick as the others
+const GRID_CENTER_THICKNESS: f32 = 3.0;
+// used while the volts per division are unknown, e.g. before the first capture
+const DEFAULT_PIXELS_PER_UNIT: f32 = 255.0;
+
+const PRIMARY_MEASUREMENTS: [&str; 4] = ["Frequency", "Vpp", "Mean", "RMS"];
+// how far a point has to stick out before Remove Spikes replaces it, in sigma of the sample to sample noise
+const DEFAULT_DESPIKE_SIGMA: f64 = 6.0;
+// the measurements shown in the measurement table, saved whenever they change
+const MEASUREMENT_TABLE_FILE: &str = "measurement_table.txt";
+// the first column of an exported waveform, the first entry follows "Time in Divisions"
+const EXPORT_TIME_COLUMNS: [&str; 4] = ["Like the Axis", "Seconds", "Samples", "Divisions"];
+// a semicolon or a tab keeps spreadsheets that use a decimal comma from splitting the columns wrong
+const EXPORT_DELIMITERS: [(&str, char); 3] = [("Comma", ','), ("Semicolon", ';'), ("Tab", '\t')];
+
+const CHANNELS: [Channel; 4] = [Channel::DISPLAY1, Channel::DISPLAY2, Channel::SAVE1, Channel::SAVE2];
+const DATA_BITS: [DataBits; 4] = [DataBits::Five, DataBits::Six, DataBits::Seven, DataBits::Eight];
+const PARITIES: [Parity; 3] = [Parity::None, Parity::Odd, Parity::Even];
 const BAUDRATES: [&str; 6] = ["300", "600", "1200", "2400", "4800", "9600"];
+const INTERPOLATION_METHODS: [&str; 6] = ["Linear", "Cosine", "Catmull-Rom", "Bézier", "Bézier Variant", "Exact Nodes"];
+// goes through every captured sample, so it interpolates the samples themselves instead of the first pass
+const EXACT_NODES_METHOD: u8 = 5;
+const FILTER_METHODS: [&str; 2] = ["None", "Savitzky-Golay"];
+// the savitzky-golay window always has to be odd and longer than the polynomial order
+const SAVGOL_WINDOW_RANGE: (usize, usize) = (5, 51);
+const SAVGOL_ORDER_RANGE: (usize, usize) = (1, 5);
+// mains frequencies for the hum filter, a low q keeps the filter settling within a few periods
+const MAINS_FREQUENCIES: [&str; 2] = ["50Hz", "60Hz"];
+const NOTCH_Q: f64 = 2.0;
+
+// bytes sent and received during one S1, Ro and Ri(0000,1000) cycle
+const CAPTURE_TRANSFER_BYTES: u32 = (3 + 2) + (6 + 68) + (17 + 1015);
+// eval_response waits this long before reading the S1 answer
+const S1_RESPONSE_DELAY_MS: u64 = 10;
+
+// presets for the acquisition timing, from quick but unreliable to slow but robust
+const ACQUISITION_PRESETS: [&str; 4] = ["Fast/noisy", "Balanced", "Slow/clean", "Custom"];
+const CUSTOM_PRESET: usize = 3;
+
+#[doc = "Number of data bits as a number"]
+fn data_bits_count(data_bits: DataBits) -> u32 {
+    match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8
+    }
+}
+
+#[doc = "Number of bits on the line for every byte with the given framing"]
+fn bits_per_byte(config: &CaptureConfig) -> u32 {
+    let stopbits = if config.two_stopbits {StopBits::Two} else {StopBits::One};
+    os3000::bits_per_byte(stopbits, config.data_bits, config.parity)
+}
+
+#[doc = "Calculates the shortest possible time between two captures with the configured baudrate and delays"]
+fn min_capture_interval(config: &CaptureConfig) -> Duration {
+    let transfer_ms = (CAPTURE_TRANSFER_BYTES * bits_per_byte(config)) as u64 * 1000 / config.baudrate as u64;
+    let delays = config.delays;
+    let delay_ms = delays.after_s1_ms + delays.before_ro_read_ms + delays.after_ro_ms + delays.before_ri_read_ms + S1_RESPONSE_DELAY_MS;
+    Duration::from_millis(transfer_ms + delay_ms)
+}
+
+#[doc = "Sets the retries, delays, read attempts and record length of one of the ACQUISITION_PRESETS"]
+fn apply_acquisition_preset(config: &mut CaptureConfig, preset: usize) {
+    // fast works on a good link at 9600 baud, slow is for long cables and the lower baudrates
+    let (max_retries, delays, ri_read_attempts, record_length) = match preset {
+        0 => (3, CommandDelays {after_s1_ms: 200, before_ro_read_ms: 500, after_ro_ms: 100, before_ri_read_ms: 300}, 2, 500),
+        1 => (0, CommandDelays::default(), os3000::DEFAULT_RI_READ_ATTEMPTS, os3000::DEFAULT_RECORD_LENGTH),
+        2 => (0, CommandDelays {after_s1_ms: 1000, before_ro_read_ms: 1500, after_ro_ms: 500, before_ri_read_ms: 1000}, 16, os3000::DEFAULT_RECORD_LENGTH),
+        _ => return
+    };
+    config.max_retries = max_retries;
+    config.delays = delays;
+    config.ri_read_attempts = ri_read_attempts;
+    config.record_length = record_length;
+}
+
+#[doc = "Link reliability since the start, filled in by the capture thread"]
+#[derive(Clone, Default)]
+struct LinkStats {
+    bytes_received: u64,
+    captures_attempted: u32,
+    captures_succeeded: u32,
+    s1_failures: u32,
+    ro_failures: u32,
+    ri_failures: u32,
+    wi_failures: u32,
+    short_reads: u32,
+    timeouts: u32,
+    empty_channels: u32
+}
+
+impl LinkStats {
+    fn count_failure(&mut self, error: &OscilloscopeError) {
+        match error {
+            OscilloscopeError::S1Failure => self.s1_failures += 1,
+            OscilloscopeError::RoError => self.ro_failures += 1,
+            OscilloscopeError::RiError | OscilloscopeError::HeaderMismatch => self.ri_failures += 1,
+            OscilloscopeError::WriteError | OscilloscopeError::CarriageReturnInData => self.wi_failures += 1,
+            OscilloscopeError::LengthMismatch{..} => self.short_reads += 1,
+            OscilloscopeError::NoResponse => self.timeouts += 1,
+            OscilloscopeError::EmptyChannel => self.empty_channels += 1
+        }
+    }
+
+    fn take_bytes(&mut self, capture: &mut OscilloscopeCapture) {
+        self.bytes_received += capture.bytes_received;
+        capture.bytes_received = 0;
+    }
+}
+
+#[doc = "Counts commands in a row that got no answer at all, returns true once the scope seems to be gone"]
+fn watchdog_tripped(silent_commands: &mut u32, error: &OscilloscopeError) -> bool {
+    if !matches!(error, OscilloscopeError::NoResponse) {
+        *silent_commands = 0;
+        return false;
+    }
+    *silent_commands += 1;
+    if *silent_commands >= NO_RESPONSE_LIMIT {
+        *silent_commands = 0;
+        return true;
+    }
+    false
+}
+
+#[doc = "When a capture is shown, like the trigger modes of a scope, the trigger is the software trigger"]
+#[derive(Clone, Copy, PartialEq)]
+enum TriggerMode {
+    // every capture, whether the trigger fires in it or not
+    Auto,
+    // only captures the trigger fires in
+    Normal,
+    // the first capture the trigger fires in, then stop
+    Single
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AutoCapture {
+    // one capture whether the trigger fires or not, then stop
+    Once,
+    Mode(TriggerMode)
+}
+
+#[doc = "What the GUI does right after launch, e.g. for a bench display that should come up showing a live trace"]
+#[derive(Default)]
+struct StartupOptions {
+    auto_capture: Option<AutoCapture>,
+    port: Option<String>,
+    channel: Option<Channel>,
+    baudrate: Option<u32>
+}
+
+#[doc = "Reads the startup options of the GUI, anything invalid is reported and ignored"]
+fn parse_startup_args(args: &[String]) -> StartupOptions {
+    let mut options = StartupOptions::default();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let Some(value) = iter.next() else {
+            eprintln!("missing value for {arg}");
+            break;
+        };
+        match arg.as_str() {
+            "--auto-capture" => options.auto_capture = match value.as_str() {
+                // single still takes one capture without waiting for the trigger, armed is the single trigger mode
+                "single" => Some(AutoCapture::Once),
+                "auto" | "continuous" => Some(AutoCapture::Mode(TriggerMode::Auto)),
+                "normal" => Some(AutoCapture::Mode(TriggerMode::Normal)),
+                "armed" => Some(AutoCapture::Mode(TriggerMode::Single)),
+                _ => {eprintln!("invalid auto capture mode {value}, use single, auto, normal or armed"); None}
+            },
+            "--port" => options.port = Some(value.clone()),
+            "--channel" => options.channel = value.parse::<u8>().ok().and_then(Channel::from_number),
+            "--baud" => options.baudrate = value.parse().ok(),
+            _ => eprintln!("unknown option {arg}")
+        }
+    }
+    options
+}
+
+#[doc = "Lists the available serial ports and picks the first USB adapter as the preferred one"]
+fn scan_ports() -> (Vec<String>, Option<String>) {
+    let mut ports: Vec<String> = Vec::new();
+    let mut preferred: Option<String> = None;
+    // a machine without any serial ports is not an error, the adapter might just not be plugged in yet
+    for port in serialport::available_ports().unwrap_or_default() {
+        if preferred.is_none() && (port.port_name.contains("/dev/ttyUSB") | port.port_name.contains("/dev/ttyACM")) {
+            preferred = Some(port.port_name.clone());
+        }
+        ports.push(port.port_name);
+    }
+    (ports, preferred)
+}
+
+#[doc = "Interpolates the samples with the method selected by its index in INTERPOLATION_METHODS"]
+fn interpolate_with_method(method: u8, samples: &Vec<f64>, num_samples: usize, time_per_div: f64, step: usize) -> Vec<f64> {
+    // 0 Linear
+    // 1 Cosine
+    // 2 Catmull-Rom
+    // 3 Bézier
+    // 4 Bézier variant 
+    // 5 Exact nodes
+    match method {
+        0 => {linear_interpolate_samples(samples, num_samples, time_per_div, step)},
+        1 => {cosine_interpolate_samples(samples, num_samples, time_per_div, step)},
+        2 => {catmull_rom_interpolate_samples(samples, num_samples, time_per_div, step)},
+        3 => {bezier_interpolate_samples(samples, num_samples, time_per_div, step)},
+        4 => {bezier2_interpolate_samples(samples, num_samples, time_per_div, step)},
+        5 => {processing::node_interpolate_samples(samples, num_samples)},
+        _ => {samples.clone()}
+    }
+}
+
+
+#[derive(Clone, PartialEq, Default)]
+struct ProcessingParams {
+    waveform: Vec<f64>,
+    time_per_div: f64,
+    interpol_samples: usize,
+    interpol_step: usize,
+    interpol2_samples: usize,
+    interpol2_step: usize,
+    interpolation_method: u8,
+    filter_method: usize,
+    savgol_window: usize,
+    savgol_order: usize,
+    notch_hz: Option<f64>,
+    notch_harmonics: usize,
+    filter_preview: bool,
+    sample_rate: f64,
+    compare_method: Option<u8>,
+    show_residual: bool,
+    reference: Vec<f64>,
+    // both in seconds, so references with another time base can be lined up
+    seconds_per_div: f64,
+    reference_seconds_per_div: f64,
+    difference: Vec<f64>,
+    extra: Vec<Vec<f64>>,
+    moving_average_voltage: Option<f64>
+}
+
+#[doc = "Everything the drawing window needs, computed ahead of time by the processing thread. Only for drawing, see gated_measurements"]
+#[derive(Clone, Default)]
+struct DisplayFrame {
+    interp_data_lin: Vec<f64>,
+    interp_data: Vec<f64>,
+    compare_data: Option<Vec<f64>>,
+    // the filtered trace while the filters are only previewed
+    preview_data: Option<Vec<f64>>,
+    // the chosen interpolation minus linear interpolation at the same points
+    residual_data: Option<Vec<f64>>,
+    reference_data: Option<Vec<f64>>,
+    // drawn points of the reference that lie within its captured span
+    reference_covered: usize,
+    difference_data: Option<Vec<f64>>,
+    extra_data: Vec<Vec<f64>>,
+    moving_average: Option<Vec<f64>>
+}
+
+#[doc = "Runs the filters that are turned on, on a copy of the waveform"]
+fn filter_waveform(params: &ProcessingParams, waveform: &Vec<f64>) -> Vec<f64> {
+    let filtered = match params.filter_method {
+        1 => processing::savgol_filter(waveform, params.savgol_window, params.savgol_order),
+        _ => waveform.clone()
+    };
+    // remove the mains frequency and as many of its harmonics as requested
+    match params.notch_hz {
+        Some(notch_hz) => (1..=params.notch_harmonics + 1).fold(filtered, |samples, harmonic| {
+            processing::notch_filter(&samples, notch_hz * harmonic as f64, params.sample_rate, NOTCH_Q)
+        }),
+        None => filtered
+    }
+}
+
+#[doc = "Both interpolation passes, returns the first (linear) pass and the points drawn with the chosen method"]
+fn reconstruct(params: &ProcessingParams, waveform: &Vec<f64>) -> (Vec<f64>, Vec<f64>) {
+    // the first pass averages down to fewer points than there are samples and only interpolates to get more
+    let interp_data_lin = processing::fit_sample_count(waveform, params.interpol2_samples, params.time_per_div, params.interpol2_step);
+    let nodes = if params.interpolation_method == EXACT_NODES_METHOD {waveform} else {&interp_data_lin};
+    let interp_data = interpolate_with_method(params.interpolation_method, nodes, params.interpol_samples, params.time_per_div, params.interpol_step);
+    (interp_data_lin, interp_data)
+}
+
+#[doc = "Runs the interpolation for a set of parameters"]
+fn process_frame(params: &ProcessingParams) -> DisplayFrame {
+    let flat_line = vec![0.0f64; MIN_WAVEFORM_SAMPLES];
+    let waveform = if params.waveform.len() < MIN_WAVEFORM_SAMPLES {&flat_line} else {&params.waveform};
+    let filtered = filter_waveform(params, waveform);
+    // while previewing, the trace stays unfiltered and the filtered version is only drawn over it
+    let (interp_data_lin, interp_data) = reconstruct(params, if params.filter_preview {waveform} else {&filtered});
+    let preview_data = if params.filter_preview {Some(reconstruct(params, &filtered).1)} else {None};
+    let waveform = &filtered;
+
+    let compare_data = params.compare_method.map(|method| {
+        interpolate_with_method(method, &interp_data_lin, params.interpol_samples, params.time_per_div, params.interpol_step)
+    });
+    let residual_data = if params.show_residual {
+        let linear = interpolate_with_method(0, &interp_data_lin, params.interpol_samples, params.time_per_div, params.interpol_step);
+        Some(processing::interp_residual(&interp_data, &linear))
+    } else {None};
+
+    // put the reference on the time axis of the live capture, a different time base stretches it
+    let (aligned_reference, reference_covered) = processing::resample_time_base(&params.reference, params.reference_seconds_per_div, waveform.len(), params.seconds_per_div);
+    let reference_data = if params.reference.is_empty() {None} else {
+        Some(linear_interpolate_samples(&aligned_reference, params.interpol_samples, params.time_per_div, 1))
+    };
+    // the drawn trace has interpol_samples points for the length of the waveform
+    let reference_covered = reference_covered * params.interpol_samples / waveform.len();
+
+    let difference_data = if params.difference.is_empty() {None} else {
+        Some(linear_interpolate_samples(&params.difference, params.interpol_samples, params.time_per_div, 1))
+    };
+
+    // too short traces stay empty so they still line up with their channels
+    let extra_data = params.extra.iter()
+        .map(|samples| if samples.len() < MIN_WAVEFORM_SAMPLES {Vec::new()} else {linear_interpolate_samples(samples, params.interpol_samples, params.time_per_div, 1)})
+        .collect();
+
+    let moving_average = params.moving_average_voltage.map(|voltage_per_div| {
+        // x * time_per_div 
+        let moving_avg = processing::make_rectangle(voltage_per_div, voltage_per_div, params.time_per_div, 3.0);
+        // sample down to main trace size
+        processing::linear_interpolate_samples(&moving_avg, params.interpol_samples, params.time_per_div, 1)
+    });
+
+    DisplayFrame{interp_data_lin, interp_data, preview_data, compare_data, residual_data, reference_data, reference_covered, difference_data, extra_data, moving_average}
+}
+
+#[doc = "Everything the capture thread hands over to the UI, behind a single lock"]
+#[derive(Default)]
+struct SharedState {
+    // finished responses, oldest first
+    responses: VecDeque<ScopeResponse>,
+    // every status change is kept, the UI shows them one per frame
+    statuses: VecDeque<ScopeStatus>,
+    dropped_frames: usize,
+    link_stats: LinkStats
+}
+
+impl SharedState {
+    fn push_response(self: &mut Self, response: ScopeResponse, depth: usize) {
+        // a stalled UI shouldn't make the queue grow, the oldest frame makes room so the UI always gets the freshest one
+        if self.responses.len() >= depth.max(1) {
+            self.responses.pop_front();
+            self.dropped_frames += 1;
+        }
+        self.responses.push_back(response);
+    }
+}
+
+#[doc = "Counts how often each cell of the drawing window was hit by the last few captures, like a digital phosphor scope"]
+#[derive(Default)]
+struct Heatmap {
+    width: usize,
+    height: usize,
+    hits: Vec<u16>,
+    // the cells hit by every trace that is still counted, oldest first
+    traces: VecDeque<Vec<usize>>,
+    // the view the cells belong to, the counts are meaningless once it changes
+    view: Vec<f32>
+}
+
+impl Heatmap {
+    fn clear(self: &mut Self) {
+        self.hits.iter_mut().for_each(|hit| *hit = 0);
+        self.traces.clear();
+    }
+
+    fn set_view(self: &mut Self, width: usize, height: usize, view: Vec<f32>) {
+        if width != self.width || height != self.height || view != self.view {
+            self.width = width;
+            self.height = height;
+            self.view = view;
+            self.hits = vec![0; width * height];
+            self.traces.clear();
+        }
+    }
+
+    fn add_trace(self: &mut Self, points: &[[f32; 2]], max_traces: usize) {
+        // the points are in cells, every cell on the line between two of them is hit once per trace
+        let mut cells: Vec<usize> = Vec::new();
+        for pair in points.windows(2) {
+            let (dx, dy) = (pair[1][0] - pair[0][0], pair[1][1] - pair[0][1]);
+            let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+            for step in 0..=steps {
+                let (x, y) = (pair[0][0] + dx * step as f32 / steps as f32, pair[0][1] + dy * step as f32 / steps as f32);
+                if x >= 0.0 && y >= 0.0 && (x as usize) < self.width && (y as usize) < self.height {
+                    cells.push(y as usize * self.width + x as usize);
+                }
+            }
+        }
+        cells.sort_unstable();
+        cells.dedup();
+        for cell in &cells {
+            self.hits[*cell] = self.hits[*cell].saturating_add(1);
+        }
+        self.traces.push_back(cells);
+        while self.traces.len() > max_traces {
+            if let Some(oldest) = self.traces.pop_front() {
+                for cell in oldest {
+                    self.hits[cell] = self.hits[cell].saturating_sub(1);
+                }
+            }
+        }
+    }
+}
+
+#[doc = "Maps 0.0 to 1.0 onto a cold to hot color ramp, blue over green and yellow to red"]
+fn heat_color(fraction: f32) -> ImColor32 {
+    let stops = [[0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]];
+    let scaled = fraction.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (scaled.floor() as usize).min(stops.len() - 2);
+    let t = scaled - i as f32;
+    let channel = |c: usize| stops[i][c] + (stops[i + 1][c] - stops[i][c]) * t;
+    ImColor32::from_rgba_f32s(channel(0), channel(1), channel(2), 0.8)
+}
+
+#[derive(Clone, Default)]
+struct MeasurementHistory {
+    stats: processing::RunningStats,
+    values: Vec<f32>
+}
+
+impl MeasurementHistory {
+    fn push(&mut self, value: f64) {
+        self.stats.push(value);
+        self.values.push(value as f32);
+        if self.values.len() > MEASUREMENT_HISTORY_LENGTH {
+            self.values.remove(0);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.stats.reset();
+        self.values.clear();
+    }
+}
+
+#[doc = "Finds the trigger point in samples as they are stored for drawing"]
+fn trigger_index(samples: &Vec<f64>, trigger: &TriggerConfig) -> Option<usize> {
+    // the samples are stored upside down, so the level and the slope have to be flipped as well
+    let slope = match trigger.slope {
+        TriggerSlope::Rising => TriggerSlope::Falling,
+        TriggerSlope::Falling => TriggerSlope::Rising
+    };
+    processing::find_trigger_index(samples, &TriggerConfig{level: -trigger.level, slope, ..*trigger})
+}
+
+#[doc = "Whether two scales are the same setting of the scope, the unit name follows from the multiplier"]
+fn same_scale(a: &ValueUnitPair, b: &ValueUnitPair) -> bool {
+    a.value == b.value && a.unit_mult == b.unit_mult
+}
+
+#[doc = "A capture as it would look at another time and voltage scale, resampled onto the other time base and converted to the other unit"]
+fn rescale_capture(samples: &[f64], from: (&ValueUnitPair, &ValueUnitPair), to: (&ValueUnitPair, &ValueUnitPair)) -> Vec<f64> {
+    let ((from_time, from_voltage), (to_time, to_voltage)) = (from, to);
+    if samples.len() < 2 || from_time.unit_mult <= 0.0 || to_time.unit_mult <= 0.0 || from_voltage.unit_mult <= 0.0 {
+        return samples.to_vec();
+    }
+    let (from_seconds, to_seconds) = (from_time.value / from_time.unit_mult, to_time.value / to_time.unit_mult);
+    let points: Vec<(f64, f64)> = samples.iter().enumerate()
+        .map(|(i, sample)| (processing::scale_time(i, from_seconds, 1.0), sample * to_voltage.unit_mult / from_voltage.unit_mult))
+        .collect();
+    // past the end of the old capture the edge values are held, like an imported CSV
+    os3000::csv::resample_points(&points, to_seconds, samples.len()).0
+}
+
+#[doc = "Returns the part of the samples that should be measured, always pass the captured samples (waveform_buffer) here"]
+fn measurement_slice(samples: &Vec<f64>, between_cursors: bool, cursor_a: usize, cursor_b: usize) -> &[f64] {
+    if !between_cursors || samples.is_empty() {
+        return &samples[..];
+    }
+    let last_index = samples.len() - 1;
+    let (start, end) = (measurement_start(samples.len(), between_cursors, cursor_a, cursor_b), cursor_a.max(cursor_b).min(last_index));
+    &samples[start..=end]
+}
+
+#[doc = "Index of the first sample measurement_slice returns, to put something found in the slice back on the capture"]
+fn measurement_start(len: usize, between_cursors: bool, cursor_a: usize, cursor_b: usize) -> usize {
+    if !between_cursors || len == 0 {
+        return 0;
+    }
+    cursor_a.min(cursor_b).min(len - 1)
+}
+
+#[doc = "The samples the slew rate is taken from, with the light smoothing if it's on"]
+fn slew_samples(samples: &[f64], smooth: bool) -> Vec<f64> {
+    if smooth {processing::savgol_filter(samples, processing::SLEW_SMOOTHING_WINDOW, 2)} else {samples.to_vec()}
+}
+
+#[doc = "Formats a slew rate in V/µs, V/ms or V/s, whichever keeps the number readable"]
+fn slew_rate_text(volts_per_second: f64) -> String {
+    let rate = volts_per_second.abs();
+    if rate >= 1E6 {format!("{:.3}V/µs", volts_per_second / 1E6)}
+    else if rate >= 1E3 {format!("{:.3}V/ms", volts_per_second / 1E3)}
+    else {format!("{:.3}V/s", volts_per_second)}
+}
+
+#[doc = "Draws a measurement line with its spread over the recent captures and a sparkline"]
+fn measurement_text(label: &str, value: Option<f64>, unit: &str, history: &MeasurementHistory, ui: &Ui) {
+    // untrustworthy measurements are shown as dashes instead of a misleading number
+    let Some(value) = value else {
+        ui.text(format!("{label}: --"));
+        return;
+    };
+    ui.text(format!("{label}: {value:.3}{unit}"));
+    if history.stats.count() > 1 {
+        ui.same_line();
+        ui.text(format!("±{:.3}", history.stats.std_dev()));
+        ui.same_line();
+        ui.plot_lines(format!("##{label}_history"), &history.values)
+            .graph_size([80.0, 14.0])
+            .build();
+    }
+}
+
+#[doc = "Formats the measurement selected by its index in PRIMARY_MEASUREMENTS with an SI prefix"]
+fn primary_measurement_text(index: usize, measurements: &MeasurementSet, voltage_per_div: &ValueUnitPair) -> String {
+    // the samples are in the scope's voltage unit, format_si wants volts
+    let to_volts = |value: f64| if voltage_per_div.unit_mult > 0.0 {value / voltage_per_div.unit_mult} else {0.0};
+    let (value, unit) = match index {
+        0 => (measurements.frequency, "Hz"),
+        1 => (measurements.vpp.map(to_volts), "V"),
+        2 => (measurements.mean.map(to_volts), "V"),
+        _ => (measurements.vrms.map(to_volts), "V")
+    };
+    match value {
+        Some(value) => processing::format_si(value, unit),
+        None => format!("--- {unit}")
+    }
+}
+
+#[doc = "Formats one measurement of the table with its unit, times the same way as everywhere else"]
+fn table_value_text(measurement: Measurement, measurements: &MeasurementSet, voltage_per_div: &ValueUnitPair, seconds_per_div: f64, in_divisions: bool) -> String {
+    let Some(value) = measurement.value(measurements) else {
+        return String::from("--");
+    };
+    measurement_value_text(measurement, value, voltage_per_div, seconds_per_div, in_divisions)
+}
+
+#[doc = "Formats a value of the given measurement in its unit, also used for the statistics over captures"]
+fn measurement_value_text(measurement: Measurement, value: f64, voltage_per_div: &ValueUnitPair, seconds_per_div: f64, in_divisions: bool) -> String {
+    let to_volts = |value: f64| if voltage_per_div.unit_mult > 0.0 {value / voltage_per_div.unit_mult} else {0.0};
+    match measurement {
+        Measurement::Vpp | Measurement::Vrms | Measurement::CycleRms | Measurement::Mean => processing::format_si(to_volts(value), "V"),
+        Measurement::Frequency => processing::format_si(value, "Hz"),
+        Measurement::DutyCycle => format!("{:.1}%", value * 100.0),
+        Measurement::CrestFactor | Measurement::FormFactor => format!("{value:.3}"),
+        _ => time_text(value, seconds_per_div, in_divisions)
+    }
+}
+
+#[doc = "Formats a time either in seconds or in horizontal divisions, like the graticule of a scope"]
+fn time_text(seconds: f64, seconds_per_div: f64, in_divisions: bool) -> String {
+    if !in_divisions {
+        return processing::format_si(seconds, "s");
+    }
+    if seconds_per_div > 0.0 {format!("{:.2} div", seconds / seconds_per_div)} else {String::from("-- div")}
+}
+
+#[doc = "Formats all measurements as plain text lines with SI units, e.g. for pasting into a notebook"]
+fn measurements_clipboard_text(measurements: &MeasurementSet, voltage_per_div: &ValueUnitPair) -> String {
+    let to_volts = |value: f64| if voltage_per_div.unit_mult > 0.0 {value / voltage_per_div.unit_mult} else {0.0};
+    let format = |value: Option<f64>, unit: &str| value.map(|value| processing::format_si(value, unit)).unwrap_or(String::from("--"));
+    let duty_cycle = measurements.duty_cycle.map(|duty| format!("{:.1}%", duty * 100.0)).unwrap_or(String::from("--"));
+    [
+        format!("Vpp: {}", format(measurements.vpp.map(to_volts), "V")),
+        format!("Mean: {}", format(measurements.mean.map(to_volts), "V")),
+        format!("RMS: {}", format(measurements.vrms.map(to_volts), "V")),
+        format!("Cycle RMS: {}", format(measurements.cycle_rms.map(to_volts), "V")),
+        format!("Freq: {}", format(measurements.frequency, "Hz")),
+        format!("Jitter: {}", format(measurements.period_jitter, "s")),
+        format!("Rise: {}", format(measurements.rise_time, "s")),
+        format!("Fall: {}", format(measurements.fall_time, "s")),
+        format!("Duty: {duty_cycle}"),
+        format!("Mark/Space: {}", measurements.mark_space.map(|mark_space| format!("{}/{} ({:.3}:1)", processing::format_si(mark_space.mark, "s"), processing::format_si(mark_space.space, "s"), mark_space.ratio)).unwrap_or(String::from("--"))),
+        format!("Crest Factor: {}", measurements.crest_factor.map(|crest| format!("{crest:.3}")).unwrap_or(String::from("--"))),
+        format!("Form Factor: {}", measurements.form_factor.map(|form| format!("{form:.3}")).unwrap_or(String::from("--")))
+    ].join("\n")
+}
+
+#[doc = "Measures the samples and blanks everything that can't be trusted, e.g. amplitudes of a clipped capture"]
+// measurements are only ever taken on the captured samples, never on the DisplayFrame:
+// splines and béziers overshoot between samples, so Vpp would depend on the chosen display interpolation
+fn gated_measurements(samples: &[f64], raw: &[u8], time_per_div: &ValueUnitPair, voltage_per_div: &ValueUnitPair, selected: &[Measurement], levels: Option<ReferenceLevels>) -> MeasurementSet {
+    let mut measurements = processing::measurements_selected(samples, time_per_div, voltage_per_div, selected, levels);
+    if !raw.is_empty() && processing::count_clipped(raw) as f32 / raw.len() as f32 > CLIP_WARNING_FRACTION {
+        measurements.blank_amplitudes();
+    }
+    measurements
+}
+
+#[doc = "Turns anti-aliasing of the current window's drawlist on or off, returns the previous flags"]
+fn set_antialiasing(enabled: bool) -> imgui::sys::ImDrawListFlags {
+    // imgui-rs doesn't expose the drawlist flags, so we have to go through imgui-sys
+    // the flags are reset from the style at the start of every frame
+    let aa_flags = (DrawListFlags::ANTI_ALIASED_LINES | DrawListFlags::ANTI_ALIASED_FILL).bits() as imgui::sys::ImDrawListFlags;
+    unsafe {
+        let draw_list = imgui::sys::igGetWindowDrawList();
+        let previous = (*draw_list).Flags;
+        if enabled {(*draw_list).Flags |= aa_flags;}
+        else {(*draw_list).Flags &= !aa_flags;}
+        previous
+    }
+}
+
+#[doc = "Restores the drawlist flags returned by set_antialiasing"]
+fn restore_draw_list_flags(flags: imgui::sys::ImDrawListFlags) {
+    unsafe {
+        (*imgui::sys::igGetWindowDrawList()).Flags = flags;
+    }
+}
+
+#[doc = "Pixels per sample unit, so that one voltage_per_div step spans one grid division of the window"]
+fn pixels_per_unit(voltage_per_div: &ValueUnitPair, window_height: f32, y_scale: f32) -> f32 {
+    // the samples are in the scope's unit times unit_mult, see processing::unit_scale
+    let units_per_div = (voltage_per_div.value * voltage_per_div.unit_mult) as f32;
+    if units_per_div <= 0.0 {
+        return DEFAULT_PIXELS_PER_UNIT * y_scale;
+    }
+    (window_height / VERTICAL_DIVISIONS) / units_per_div * y_scale
+}
+
+#[doc = "The placement a channel's traces are drawn with, a locked baseline keeps 0V on the center line"]
+fn placed_display(channel_display: &[ChannelDisplay; 4], channel: Channel, lock_baseline: bool) -> ChannelDisplay {
+    let display = channel_display[channel.index()];
+    if lock_baseline {ChannelDisplay {y_offset: 0.0, ..display}} else {display}
+}
 
+#[doc = "Maps a sample to its y coordinate in the current window, a sample of 0.0 sits on the center line"]
+fn sample_to_y(sample: f64, pixels_per_unit: f32, y_offset: f32, ui: &Ui) -> f32 {
+    let (_, win_y) = ui.window_pos().into();
+    //     sample data                       center trace at window center                  y offset
+    (sample as f32 * pixels_per_unit) + (win_y + 5.0) + (ui.window_size()[1]/2.0) + y_offset
+}
 
 #[doc = "Draws a trace on a window using the drawlist"]
-fn draw_trace_lines(samples: &Vec<f64>, drawlist: &DrawListMut, c: ImColor32, start_index: usize,end_index: usize, offsets: (f32, f32), scales: (f32, f32),thickness:f32 ,ui: &Ui) {
+fn draw_trace_lines(samples: &Vec<f64>, drawlist: &DrawListMut, start_index: usize, end_index: usize, style: &TraceStyle, ui: &Ui) {
     // all our samples are f64, imgui unfortunately only wants f32
-    let mut last_point = [0.0, samples[start_index] as f32];
-    let (win_x, win_y) = ui.window_pos().into();
+    let (win_x, _) = ui.window_pos().into();
+    let points: Vec<[f32; 2]> = (start_index..end_index)
+        .map(|i| [win_x + (i - start_index) as f32 * style.scales.0 + style.offsets.0, sample_to_y(samples[i], style.scales.1, style.offsets.1, ui)])
+        .collect();
+    // the whole trace in one polyline, one primitive per segment is a lot of overhead for dense traces
+    if points.len() >= 2 {
+        drawlist.add_polyline(points, style.color).thickness(style.thickness).build();
+    }
+}
+
+#[doc = "Draws a trace one line per segment, the way it was drawn before batching, kept around for comparing the two"]
+fn draw_trace_segments(samples: &Vec<f64>, drawlist: &DrawListMut, start_index: usize, end_index: usize, style: &TraceStyle, ui: &Ui) {
+    let mut last_point: Option<[f32; 2]> = None;
+    let (win_x, _) = ui.window_pos().into();
+    for i in start_index..end_index {
+        //               window x coordinate + current index - start index       + sub-pixel shift
+        let new_x = win_x + (i - start_index) as f32 * style.scales.0 + style.offsets.0;
+        let new_y = sample_to_y(samples[i], style.scales.1, style.offsets.1, ui);
+        // the first point has nothing to connect to
+        if let Some(point) = last_point {
+            drawlist.add_line(point, [new_x, new_y], style.color).thickness(style.thickness).build();
+        }
+        last_point = Some([new_x, new_y]);
+    }
+}
+
+#[doc = "Shades the area between a trace and the center line using the drawlist"]
+fn draw_trace_fill(samples: &Vec<f64>, drawlist: &DrawListMut, start_index: usize, end_index: usize, style: &TraceStyle, ui: &Ui) {
+    let (win_x, _) = ui.window_pos().into();
+    // the center line is where a sample of 0.0 ends up
+    let ground_y = sample_to_y(0.0, style.scales.1, style.offsets.1, ui);
+    let mut last_point: Option<[f32; 2]> = None;
     for i in start_index..end_index {
-        //               window x coordinate + current index - start index       
-        let new_x = win_x + (i - start_index) as f32 * scales.0;
-        //                sample data              center trace at window cetner                   y scale            y offset
-        let new_y = (((samples[i] as f32 * 255.0 * scales.1) + (win_y + 5.0) + (ui.window_size()[1]/2.0)) ) + offsets.1;
-        drawlist.add_line(last_point, [new_x, new_y], c).thickness(thickness).build();
-        last_point = [new_x, new_y];
+        let new_x = win_x + (i - start_index) as f32 * style.scales.0 + style.offsets.0;
+        let new_y = sample_to_y(samples[i], style.scales.1, style.offsets.1, ui);
+        // every segment becomes a quad down to the center line, made out of two triangles
+        if let Some(point) = last_point {
+            drawlist.add_triangle(point, [new_x, new_y], [new_x, ground_y], style.color).filled(true).build();
+            drawlist.add_triangle(point, [new_x, ground_y], [point[0], ground_y], style.color).filled(true).build();
+        }
+        last_point = Some([new_x, new_y]);
+    }
+}
+
+#[doc = "Draws the raw samples as stems from the center line at their actual positions on the drawn trace"]
+fn draw_raw_stems(samples: &Vec<f64>, display_len: usize, drawlist: &DrawListMut, index_start: usize, style: &TraceStyle, ui: &Ui) {
+    let (win_x, _) = ui.window_pos().into();
+    let window_right = win_x + ui.window_size()[0];
+    let ground_y = sample_to_y(0.0, style.scales.1, style.offsets.1, ui);
+    for (i, sample) in samples.iter().enumerate() {
+        let x = raw_index_to_x(i, samples.len(), display_len, index_start, style.scales.0, win_x + style.offsets.0);
+        // only the visible part of the capture
+        if x < win_x {
+            continue;
+        }
+        if x > window_right {
+            break;
+        }
+        let y = sample_to_y(*sample, style.scales.1, style.offsets.1, ui);
+        drawlist.add_line([x, ground_y], [x, y], style.color).build();
+        drawlist.add_circle([x, y], 2.0, style.color).filled(true).build();
+    }
+}
+
+#[doc = "Draws the raw samples as a line at their positions on the drawn trace, for captures that aren't interpolated"]
+fn draw_raw_trace(samples: &Vec<f64>, display_len: usize, drawlist: &DrawListMut, index_start: usize, style: &TraceStyle, ui: &Ui) {
+    let (win_x, _) = ui.window_pos().into();
+    let window_right = win_x + ui.window_size()[0];
+    let mut last_point: Option<[f32; 2]> = None;
+    for (i, sample) in samples.iter().enumerate() {
+        let x = raw_index_to_x(i, samples.len(), display_len, index_start, style.scales.0, win_x + style.offsets.0);
+        let y = sample_to_y(*sample, style.scales.1, style.offsets.1, ui);
+        // only the segments that reach into the window
+        if let Some(point) = last_point.filter(|_| x >= win_x) {
+            drawlist.add_line(point, [x, y], style.color).build();
+        }
+        if x > window_right {
+            break;
+        }
+        last_point = Some([x, y]);
+    }
+}
+
+#[doc = "Draws the raw samples as a staircase, every sample is held until the next one, like the scope's DAC would"]
+fn draw_raw_hold(samples: &Vec<f64>, display_len: usize, drawlist: &DrawListMut, index_start: usize, style: &TraceStyle, ui: &Ui) {
+    let (win_x, _) = ui.window_pos().into();
+    let window_right = win_x + ui.window_size()[0];
+    let mut points: Vec<[f32; 2]> = Vec::new();
+    for (i, sample) in samples.iter().enumerate() {
+        let x = raw_index_to_x(i, samples.len(), display_len, index_start, style.scales.0, win_x + style.offsets.0);
+        let next_x = raw_index_to_x(i + 1, samples.len(), display_len, index_start, style.scales.0, win_x + style.offsets.0);
+        // only the steps that reach into the window
+        if next_x < win_x {
+            continue;
+        }
+        let y = sample_to_y(*sample, style.scales.1, style.offsets.1, ui);
+        // the vertical step is the line from the end of the last step to the start of this one
+        points.push([x, y]);
+        points.push([next_x, y]);
+        if next_x > window_right {
+            break;
+        }
+    }
+    if points.len() >= 2 {
+        drawlist.add_polyline(points, style.color).thickness(style.thickness).build();
     }
 }
 
+#[doc = "How much of the trace color is left in an afterglow trace, the rest has faded into the background"]
+fn afterglow_weight(age: usize, traces: usize, exponential: bool) -> f32 {
+    // age 0 is the capture right before the current one
+    let fraction = (age + 1) as f32 / (traces + 1) as f32;
+    if exponential {(-4.0 * fraction).exp()} else {1.0 - fraction}
+}
+
 #[doc = "Draws a trace on a window using the drawlist"]
-fn draw_trace_dots(samples: &Vec<f64>, drawlist: &DrawListMut, c: ImColor32, start_index: usize,end_index: usize, offsets: (f32, f32), scales: (f32, f32),thickness:f32 ,ui: &Ui) {
+fn draw_trace_dots(samples: &Vec<f64>, drawlist: &DrawListMut, start_index: usize, end_index: usize, style: &TraceStyle, ui: &Ui) {
     // all our samples are f64, imgui unfortunately only wants f32
-    let (win_x, win_y) = ui.window_pos().into();
+    let (win_x, _) = ui.window_pos().into();
     
     for i in start_index..end_index {
-        //               window x coordinate + current index - start index       
-        let new_x = win_x + (i - start_index) as f32 * scales.0;
-        //                sample data              center trace at window cetner        y scale                       y offset
-        let new_y = (((samples[i] as f32 * 255.0 * scales.1) + (win_y + 5.0) + (ui.window_size()[1]/2.0)) ) + offsets.1;
-        drawlist.add_circle([new_x, new_y], thickness, c).filled(true).build();
+        //               window x coordinate + current index - start index       + sub-pixel shift
+        let new_x = win_x + (i - start_index) as f32 * style.scales.0 + style.offsets.0;
+        let new_y = sample_to_y(samples[i], style.scales.1, style.offsets.1, ui);
+        drawlist.add_circle([new_x, new_y], style.thickness, style.color).filled(true).build();
+    }
+}
+
+#[doc = "Slider that can be ctrl-clicked to type an exact value, which is kept within min and max"]
+fn precise_slider<K: imgui::internal::DataTypeKind>(ui: &Ui, label: &str, min: K, max: K, value: &mut K) -> bool {
+    let changed = ui.slider_config(label, min, max).flags(SliderFlags::ALWAYS_CLAMP).build(value);
+    if ui.is_item_hovered() {
+        ui.tooltip_text("Ctrl+Click to type a value");
+    }
+    changed
+}
+
+#[doc = "Width of one column of control windows, they take up to 300 pixels each and shrink on small displays"]
+fn control_column_width(display_width: f32) -> f32 {
+    (display_width * CONTROL_COLUMN_FRACTION).clamp(CONTROL_COLUMN_MIN_WIDTH, 300.0).min(display_width / 2.0)
+}
+
+#[doc = "First pass samples in auto mode, the samples as they are, a deep record averaged down to twice a single record"]
+fn auto_interpol2_samples(waveform_len: usize) -> usize {
+    // as many first pass samples as captured ones skip the first pass, fewer take the decimating path in reconstruct
+    processing::auto_first_pass_samples(waveform_len).min(INTERPOL2_SAMPLES_RANGE.1)
+}
+
+#[doc = "Scales an interpolation sample count by the performance cap, without going below the slider's minimum"]
+fn capped_samples(samples: usize, cap: f32, min: usize) -> usize {
+    ((samples as f32 * cap) as usize).max(min).min(samples)
+}
+
+#[doc = "Maps an index into the raw waveform to an x coordinate on the drawn (interpolated) trace"]
+fn raw_index_to_x(raw_index: usize, raw_len: usize, display_len: usize, index_start: usize, x_scale: f32, win_x: f32) -> f32 {
+    let display_index = raw_index as f32 * (display_len as f32 / raw_len as f32);
+    win_x + (display_index - index_start as f32) * x_scale
+}
+
+#[doc = "The sample closest to a fractional raw index, positions outside of the capture stick to its ends"]
+fn nearest_sample(position: f64, len: usize) -> Option<usize> {
+    if len == 0 || !position.is_finite() {
+        return None;
     }
+    Some(position.round().clamp(0.0, (len - 1) as f64) as usize)
 }
 
 #[doc = "Update the start index for dragging the waveform on screen"]
@@ -109,52 +1020,140 @@ fn update_start_index(index_end:usize, index_start: &mut usize, ui: &Ui, x_scale
 }
 
 #[doc = "Draws a 5x4 grid"]
-fn draw_grid_lines(line_color: ImColor32, y_offset: f32, ui: &Ui ,draw_list: &DrawListMut) {
+fn draw_grid_lines(line_color: ImColor32, y_offset: f32, thickness: f32, ui: &Ui ,draw_list: &DrawListMut) {
     let (win_x, win_y) = ui.window_pos().into();
     let (window_width, window_height) = ui.window_size().into();
+    let scale = ui.io().display_framebuffer_scale[0];
+    // without anti-aliasing the snapped lines cover exactly the pixels they're on
+    let draw_list_flags = set_antialiasing(false);
 
     // draw vertical lines 
     for i in 1..10 {
-        let offset = (window_width / 10.0) * i as f32;
-        if i != 5 {draw_list.add_line([win_x + offset, win_y + y_offset], [win_x + offset, win_y + window_height + y_offset], line_color).build();}
         // make center line thicker
-        else {draw_list.add_line([win_x + offset, win_y + y_offset], [win_x + offset, win_y + window_height + y_offset], line_color).thickness(4.0).build();}
+        let line_thickness = if i != 5 {thickness} else {thickness * GRID_CENTER_THICKNESS};
+        let x = snap_to_pixel(win_x + (window_width / 10.0) * i as f32, line_thickness, scale);
+        draw_list.add_line([x, win_y + y_offset], [x, win_y + window_height + y_offset], line_color).thickness(snapped_thickness(line_thickness, scale)).build();
     }
     // draw horizontal_lines
     for i in 1..8 {
-        let offset = (window_height / 8.0) * i as f32;
-        if i != 4 {draw_list.add_line([win_x, win_y + offset + y_offset], [win_x + window_width, win_y + offset + y_offset], line_color).build();}
         // make center line thicker
-        else {draw_list.add_line([win_x, win_y + offset + y_offset], [win_x + window_width, win_y + offset + y_offset], line_color).thickness(3.0).build();}
+        let line_thickness = if i != 4 {thickness} else {thickness * GRID_CENTER_THICKNESS};
+        let y = snap_to_pixel(win_y + (window_height / 8.0) * i as f32 + y_offset, line_thickness, scale);
+        draw_list.add_line([win_x, y], [win_x + window_width, y], line_color).thickness(snapped_thickness(line_thickness, scale)).build();
     }
+    restore_draw_list_flags(draw_list_flags);
+}
+
+#[doc = "Rounds a line thickness to whole physical pixels, at least one"]
+fn snapped_thickness(thickness: f32, scale: f32) -> f32 {
+    (thickness * scale).round().max(1.0) / scale
+}
+
+#[doc = "Moves a line coordinate onto the physical pixel grid, so the line doesn't get smeared over two pixels"]
+fn snap_to_pixel(coordinate: f32, thickness: f32, scale: f32) -> f32 {
+    // lines an odd number of pixels wide are centered on a pixel, even ones on the border between two
+    let pixels = (thickness * scale).round().max(1.0);
+    let center = if pixels as u32 % 2 == 1 {0.5} else {0.0};
+    ((coordinate * scale - center).round() + center) / scale
 }
 
 
 #[allow(unused_mut)]
 fn main() {
+    // scripted captures skip the GUI entirely
+    let args: Vec<String> = std::env::args().collect();
+    if headless::requested(&args) {
+        std::process::exit(headless::run(&args));
+    }
+
     let mut do_capture    : bool = false;
-    let mut single_capture: bool = false;
+    let mut trigger_mode = TriggerMode::Auto;
+    // one capture without waiting for the trigger, whatever the trigger mode
+    let mut capture_once = false;
 
-    let mut port_string:      String = String::from("");
     let mut status_string = "Idle";
 
     let mut waveform_buffer: Vec<f64> = vec![0.0f64; 1000];
-    let mut availible_ports: Vec<String> = Vec::new();
+    let mut raw_buffer: Vec<u8> = Vec::new();
+    let mut capture_conditions: Option<CaptureConditions> = None;
 
     let mut channel: Channel = Channel::DISPLAY1;
     let mut mode_radiobutton:u8 = 2;
+    let mut store_channel: Option<Channel> = None;
+    // why the last store to a save channel wasn't sent
+    let mut store_message: Option<String> = None;
+    let mut read_settings = false;
+    // the defaults of the capture config are the balanced preset
+    let mut acquisition_preset: usize = 1;
+    let mut no_response = false;
+    // how far the blocks of a deep record have come
+    let mut deep_progress: Option<(usize, usize)> = None;
+    // the panic message of the capture thread, until the next successful command
+    let mut internal_error: Option<String> = None;
+    let mut show_conditions = false;
+    let mut last_capture_time: Option<Instant> = None;
+    let mut periodic_capture = false;
+    let mut capture_period_secs: f32 = 10.0;
+    let mut last_good_capture: Option<Instant> = None;
+    let mut clear_on_failure = false;
+    let mut trace_stale = false;
+    // when the scope reports another scale than the trace on screen was captured at, redraw the trace at it or clear it
+    let mut rescale_on_change = true;
+    let mut settings_changed = false;
+    let mut trigger = TriggerConfig::default();
+    // whether the trigger fired since the capture was started, in normal and single mode
+    let mut triggered = false;
+    let mut stabilize = false;
+    let mut stabilize_reference: Vec<f64> = Vec::new();
+    let mut stabilize_shift: isize = 0;
+    // where the software trigger fired in the current capture, in raw samples
+    let mut show_trigger_point = false;
+    let mut shade_pre_trigger = true;
+    let mut trigger_position: f32 = 0.2;
+    let mut trigger_sample: Option<usize> = None;
+    let mut align_trigger = false;
+    let mut capture_interval: Option<Duration> = None;
+    let mut diagnostics_report: Option<DiagnosticsReport> = None;
+    let mut show_diagnostics = false;
 
     let mut x_scale: f32 = 1.0;
-    let mut y_scale: f32 = 1.0;
-
-    let mut y_offset: f32 = 0.0;
-    let mut x_offset: usize = 0;
+    // how far past index_start the view begins, in samples of the drawn trace, so zooming doesn't snap to whole samples
+    let mut view_fraction: f32 = 0.0;
+    let mut smooth_zoom = true;
+    // scroll pans by this fraction of the screen per notch, ctrl/shift + scroll zoom by this much per notch
+    let mut scroll_pan_speed: f32 = 0.1;
+    let mut scroll_zoom_speed: f32 = 0.1;
+    // one vertical offset and scale per channel, so traces from different channels can be stacked
+    let mut channel_display = [ChannelDisplay::default(); 4];
+    let mut lock_baseline = false;
+    // the other channels of a multi channel capture and the colors they are drawn in
+    let mut extra_traces: Vec<ChannelCapture> = Vec::new();
+    let mut channel_colors = [
+        Vector4::from([1.0,0.1,0.1,1.0]),
+        Vector4::from([0.1,0.9,0.3,1.0]),
+        Vector4::from([0.3,0.6,1.0,1.0]),
+        Vector4::from([1.0,0.6,0.1,1.0])
+    ];
 
     let mut interpol_samples: usize = 1000;
     let mut interpol_step: usize = 2;
-    let mut interpol2_samples: usize = waveform_buffer.len() * 2;
+    let mut interpol2_samples: usize = auto_interpol2_samples(waveform_buffer.len());
+    // follow the length of the captures until the user picks a count
+    let mut interpol2_auto = true;
     let mut interpol2_step: usize = 1;
     let mut interpolation_method: u8 = 0;
+    let mut compare_methods = false;
+    let mut compare_method: usize = 0;
+    let mut show_residual = false;
+    let mut filter_preview = false;
+    // the residual is small next to the trace, so it's drawn this many times larger
+    let mut residual_gain: f32 = 10.0;
+    let mut filter_method: usize = 0;
+    let mut savgol_window: usize = 11;
+    let mut savgol_order: usize = 2;
+    let mut remove_hum = false;
+    let mut mains_frequency: usize = 0;
+    let mut hum_harmonics: usize = 0;
 
     let mut avg_window_size: usize = 3;
     let mut max_window_size: usize = 1000;
@@ -164,151 +1163,560 @@ fn main() {
 
     let mut index_start: usize = 0;
 
+    let mut last_params = ProcessingParams::default();
+    let mut display_frame = DisplayFrame::default();
+
     let mut draw_average = false;
     let mut draw_main_trace = true;
     let mut draw_grid = true;
-    let mut snap_to_trace = false;
+    let mut snap_mode = SnapMode::None;
+    // the nearest sample of the trace under the mouse in a tooltip
+    let mut hover_tooltip = true;
     let mut draw_dots = false;
+    let mut draw_fill = false;
+    let mut draw_stems = false;
+    let mut draw_hold = false;
+    // the capture before the current one, to show what changed
+    let mut previous_buffer: Vec<f64> = Vec::new();
+    let mut draw_difference = false;
+    let mut fill_alpha: f32 = 0.3;
+    let mut highlight_clipping = true;
+    let mut mark_peaks = false;
+    let mut slew_smoothing = false;
+    let mut mark_slew = false;
+    let mut draw_heatmap = false;
+    let mut heatmap_traces: usize = 50;
+    let mut heatmap = Heatmap::default();
+    // set for every new capture, the drawing window adds it to the heatmap
+    let mut heatmap_new_trace = false;
 
     let mut trace_thickness: f32 = 2.0;
     let mut avg_thickness: f32 = 2.0;
+    let mut trace_antialiasing = true;
 
     let mut trace_color = Vector4::from([1.0,0.1,0.1,1.0]);
     let mut avg_color = Vector4::from([0.1,0.1,1.0,1.0]);
+    let mut compare_color = Vector4::from([0.1,1.0,0.1,1.0]);
+    let mut reference_color = Vector4::from([0.8,0.8,0.8,0.6]);
+    let mut difference_color = Vector4::from([1.0,0.9,0.2,0.8]);
+    let mut background_color = Vector4::from([10.0 / 255.0, 10.0 / 255.0, 10.0 / 255.0, 1.0]);
+
+    // older captures fade out behind the current one, like the afterglow of a phosphor screen
+    let mut draw_afterglow = false;
+    let mut afterglow_traces: usize = 8;
+    let mut afterglow_curve: usize = 0;
+    let mut afterglow_grid_opacity = false;
+    let mut afterglow: VecDeque<Vec<f64>> = VecDeque::new();
+
+    let mut reference_buffer: Vec<f64> = Vec::new();
+    let mut reference_time_per_div: ValueUnitPair = ValueUnitPair::default();
+    let mut draw_reference = true;
+    let mut import_path = String::new();
+    let mut export_path = String::from("capture.csv");
+    let mut export_message = String::new();
+    let mut export_time_column: usize = 0;
+    let mut export_delimiter: usize = 0;
+    let mut export_limit_decimals = false;
+    let mut export_decimals: usize = 6;
+    // the SVG export redraws the view of the drawing window from the last frame at this size
+    let mut svg_size: [i32; 2] = [1200, 800];
+    let mut export_view: Option<SvgView> = None;
+
+    // every capture can be appended to a recording, which can be stepped through again later
+    let mut record_path = String::from("session.oscp");
+    let mut recording = false;
+    let mut recorded_captures: usize = 0;
+    let mut record_message = String::new();
+    let mut replay: Vec<CaptureRecord> = Vec::new();
+    let mut replay_position: usize = 0;
+    let mut replay_shown: Option<usize> = None;
+    let mut replay_playing = false;
+    // captures per second while playing
+    let mut replay_rate: f32 = 2.0;
+    let mut last_replay_step = Instant::now();
+    // the most recent captures kept in memory, oldest first, so a glitch can be found again without a recording
+    let mut scrollback: VecDeque<(Instant, CaptureRecord)> = VecDeque::new();
+    let mut scrollback_length: usize = 50;
+    // which of them is shown instead of the live capture
+    let mut scrollback_view: Option<usize> = None;
+    let mut scrollback_shown: Option<usize> = None;
+    let mut reference_channel: Channel = Channel::DISPLAY1;
+    let mut import_message = String::new();
     let mut grid_opacity: u8 = 128;
+    let mut grid_thickness: f32 = 1.0;
+
+    let mut show_eye = false;
+    let mut eye_auto_period = true;
+    let mut eye_period: usize = 50;
+    let mut eye_persistence: usize = 200;
+    let mut eye_slices: Vec<Vec<f64>> = Vec::new();
+
+    let mut show_spectrogram = false;
+    // the spectrum of every capture, newest first, and the bin width of the newest one
+    let mut spectrogram: VecDeque<Vec<f64>> = VecDeque::new();
+    let mut spectrogram_bin_width: f64 = 0.0;
+    let mut spectrogram_range_db: f32 = 60.0;
+    let mut spectrum_message = String::new();
+
+    let mut show_ideal = false;
+    // an ideal shape over the capture, fitted to every new capture unless it's set by hand
+    let mut ideal_shape = IdealShape::Sine;
+    let mut ideal_auto_fit = true;
+    let mut ideal_fit = IdealFit::default();
+
+    let mut average_captures = false;
+    let mut average_count: usize = 8;
+    let mut averaged_captures: usize = 0;
+    let mut average_buffer: Vec<f64> = Vec::new();
+
+    let mut show_magnifier = false;
+    let mut magnifier_span: usize = 20;
+    let mut hovered_raw_index: Option<f64> = None;
+
+    let mut show_measurements = false;
+    let mut draw_cursors = false;
+    let mut measure_between_cursors = false;
+    let mut cursor_a: usize = 250;
+    let mut cursor_b: usize = 750;
+    let mut vpp_history = MeasurementHistory::default();
+    let mut mean_history = MeasurementHistory::default();
+    let mut rms_history = MeasurementHistory::default();
+    let mut cycle_rms_history = MeasurementHistory::default();
+    let mut load_impedance: f64 = 50.0;
+    let mut settling_tolerance: f64 = 5.0;
+    let mut pulse_slope = TriggerSlope::Rising;
+    // high and low level for the edge and pulse measurements, None finds them from the signal
+    let mut reference_levels: Option<ReferenceLevels> = None;
+    let mut manual_levels = ReferenceLevels{low: -1.0, high: 1.0};
+    // the DC readout averages this many raw samples, ending at the right edge of the drawing window
+    let mut dc_window: usize = 100;
+    let mut displayed_raw_end: usize = 0;
+    let mut probe_time = String::new();
+
+    // show horizontal readouts in divisions (0-10) instead of seconds
+    let mut time_in_divisions = false;
+
+    let mut show_measurement_table = false;
+    // only these are measured for the table, in this order
+    let mut table_measurements: Vec<Measurement> = std::fs::read_to_string(MEASUREMENT_TABLE_FILE)
+        .map(|text| processing::parse_measurement_list(&text))
+        .unwrap_or(vec![Measurement::Vpp, Measurement::Vrms, Measurement::Mean, Measurement::Frequency]);
+    let mut table_selected: Option<usize> = None;
+
+    // statistics of one measurement over many captures, only collected while running
+    let mut show_capture_stats = false;
+    let mut capture_stats_running = false;
+    let mut capture_stats_measurement = Measurement::Frequency;
+    let mut capture_stats = processing::RunningStats::default();
+
+    let mut show_counter = false;
+    let mut primary_measurement: usize = 0;
+    let mut counter_font_scale: f32 = 4.0;
+
+    let mut show_performance = false;
+    let mut frame_times: Vec<f32> = Vec::with_capacity(FRAME_TIME_HISTORY_LENGTH);
+    // scales both interpolation sample counts down while the frames take longer than the target
+    let mut auto_sample_cap = false;
+    let mut frame_time_target: f32 = 16.0;
+    let mut sample_cap: f32 = 1.0;
+    let mut last_cap_adjust = Instant::now();
+
+    let mut show_link_stats = false;
+    // bytes per second, measured over roughly one second
+    let mut byte_rate: f64 = 0.0;
+    let mut byte_rate_sample: (u64, Instant) = (0, Instant::now());
+
+    // how long the main trace took to draw, to compare batched lines with one line per segment
+    let mut batch_lines = true;
+    let mut trace_draw_time: f32 = 0.0;
 
     let mut show_demo = true;
-
-    for port in serialport::available_ports().expect("No Ports found") {
-        // 
-        if port.port_name.contains("/dev/ttyUSB") | port.port_name.contains("/dev/ttyACM") {
-            port_string = port.port_name.clone();
+    // the control windows are pinned next to the drawing window, unlocked they can be moved and docked freely
+    let mut lock_layout = true;
+
+    let (mut availible_ports, preferred_port) = scan_ports();
+    let startup = parse_startup_args(&args);
+    let mut port_string: String = startup.port.clone().or(preferred_port).unwrap_or_default();
+    // only start capturing on our own if the port is actually there, otherwise wait for the user
+    let mut startup_message: Option<String> = None;
+    if let Some(auto_capture) = startup.auto_capture {
+        if availible_ports.contains(&port_string) {
+            do_capture = true;
+            match auto_capture {
+                AutoCapture::Once => capture_once = true,
+                AutoCapture::Mode(mode) => trigger_mode = mode
+            }
         }
-
-        availible_ports.push(port.port_name);
+        else {
+            startup_message = Some(format!("Port {} not found, not capturing on startup", if port_string.is_empty() {"(none)"} else {port_string.as_str()}));
+        }
+    }
+    if let Some(startup_channel) = startup.channel {
+        channel = startup_channel;
     }
-    let (waveform_tx,waveform_rx): (Sender<ScopeResponse>, Receiver<ScopeResponse>) = mpsc::channel();
-    let (status_tx, status_rx): (Sender<ScopeStatus>, Receiver<ScopeStatus>) = mpsc::channel();
+    // responses, status changes and link stats from the capture thread
+    let shared: Arc<Mutex<SharedState>> = Arc::new(Mutex::new(SharedState::default()));
+    let shared_guard: Arc<Mutex<SharedState>> = Arc::clone(&shared);
 
     let config_mutex: Arc<Mutex<CaptureConfig>> = Arc::new(Mutex::new(CaptureConfig {
         do_capture: false,
         open_port: false,
         two_stopbits: false,
+        data_bits: DataBits::Eight,
+        parity: Parity::None,
         do_ri: true,
         do_s1: false,
         do_ro: false,
+        do_wi: false,
+        do_diag: false,
         port_name: port_string.clone(),
-        channel: Channel::DISPLAY1,
-        baudrate: 9600,
+        channel,
+        wi_channel: Channel::SAVE1,
+        wi_data: Vec::new(),
+        baudrate: startup.baudrate.unwrap_or(9600),
+        record_length: os3000::DEFAULT_RECORD_LENGTH,
+        sample_format: SampleFormat::default(),
+        ri_read_attempts: os3000::DEFAULT_RI_READ_ATTEMPTS,
+        ri_header_length: None,
+        delays: CommandDelays::default(),
+        max_retries: 0,
+        queue_depth: 4,
+        capture_period: None,
+        acquire_channels: [false; 4],
+        deep_record: None,
+        calibration: [Calibration::default(); 4],
+        despike_sigma: None,
     }));
 
     let config_mutex_guard: Arc<Mutex<CaptureConfig>> = Arc::clone(&config_mutex);
 
-    
-    // Data Capture thread
-    thread::spawn(move || {
+    // the processing thread turns the parameters into a ready to draw frame, so the UI never waits on the interpolation
+    let (params_tx, params_rx): (Sender<ProcessingParams>, Receiver<ProcessingParams>) = mpsc::channel();
+    let frame_mutex: Arc<Mutex<Option<DisplayFrame>>> = Arc::new(Mutex::new(None));
+    let frame_mutex_guard: Arc<Mutex<Option<DisplayFrame>>> = Arc::clone(&frame_mutex);
 
-        let mut config: CaptureConfig;
-        let mut stopbits: StopBits;
-        'thread_loop: loop {
-            // copy config from mutex
-            if let Ok(ref mut mutex) = config_mutex_guard.try_lock() {
-                config = (**mutex).clone();
+    // Processing thread
+    thread::spawn(move || {
+        while let Ok(mut params) = params_rx.recv() {
+            // only the newest parameters matter
+            while let Ok(newer) = params_rx.try_recv() {
+                params = newer;
             }
-            else {
-                // if we can't lock it, restart the loop, effectively spinlocking until we can get the config
-                continue 'thread_loop;
+            let frame = process_frame(&params);
+            if let Ok(mut latest) = frame_mutex_guard.lock() {
+                *latest = Some(frame);
             }
+        }
+    });
+
+    
+    // set when the window closes, the capture thread finishes what it's doing and drops the port
+    let shutdown: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let shutdown_guard: Arc<AtomicBool> = Arc::clone(&shutdown);
+
+    // Data Capture thread
+    let capture_thread = thread::spawn(move || {
+        // a panic anywhere in here would end the thread silently and the UI would just stop updating
+        let mut capture_loop = || {
+            let mut config: CaptureConfig;
+            let mut stopbits: StopBits;
+            let mut last_capture_done: Option<Instant> = None;
+            // a flaky link still sends something, a scope that's off or in local mode sends nothing
+            let mut silent_commands: u32 = 0;
+            let mut failed_captures: u32 = 0;
+            let send_status = |status: ScopeStatus| {
+                if let Ok(mut state) = shared_guard.lock() {
+                    state.statuses.push_back(status);
+                }
+            };
+            let send_response = |response: ScopeResponse, depth: usize| {
+                if let Ok(mut state) = shared_guard.lock() {
+                    state.push_response(response, depth);
+                }
+            };
+            'thread_loop: loop {
+                if shutdown_guard.load(Ordering::SeqCst) {
+                    break 'thread_loop;
+                }
+
+                // copy config from mutex, this waits for the UI to finish with it instead of spinning
+                match config_mutex_guard.lock() {
+                    Ok(mutex) => config = (*mutex).clone(),
+                    // the UI thread panicked while holding it, there is nobody left to capture for
+                    Err(_) => break 'thread_loop
+                }
             
-            // check if the port should be opened
-            if config.open_port {
-                // get the correct stop bit value
-                if config.two_stopbits {stopbits = StopBits::Two;}
-                else {stopbits = StopBits::One;}
-                
-                // initialize the capture
-                let mut capture = OscilloscopeCapture::new(
-                    &config.port_name.as_str(),
-                    config.baudrate,
-                    stopbits
-                );
-
-                // handle commands 
-                if config.do_capture {
-                    // create an empty response object
-                    let mut response = ScopeResponse{
-                        s1_result: false,
-                        capture_conditions: String::new(),
-                        waveform_data: Vec::<f64>::with_capacity(1000),
-                        time_per_div: ValueUnitPair::default(),
-                        voltage_per_div: ValueUnitPair::default()
-                    };
-                    
-                    if config.do_s1 {
-                        if let Err(e) = capture.send_s1() {
-                            eprintln!("S1 Failed: {e}");
-                            response.s1_result = false;
-                            // Send S1 failure status message
-                            status_tx.send(ScopeStatus::S1Fail).unwrap();
-                            waveform_tx.send(response).unwrap();
+                // check if the port should be opened
+                if config.open_port {
+                    // when capturing periodically, leave the scope alone until the interval is over
+                    if let Some(period) = config.capture_period {
+                        if last_capture_done.is_some_and(|done| done.elapsed() < period) {
+                            sleep(Duration::from_millis(100));
                             continue 'thread_loop;
                         }
-                        response.s1_result = true;
-                        continue 'thread_loop;                        
                     }
-                    else if config.do_ri {
-                        status_tx.send(ScopeStatus::Ri).unwrap();
 
-                        match capture.get_waveform_data(config.channel, 1.0) {
-                            Ok(data) => {
-                                response.voltage_per_div = data.2;
-                                response.time_per_div = data.1;
-                                response.waveform_data = data.0;
+                    // get the correct stop bit value
+                    if config.two_stopbits {stopbits = StopBits::Two;}
+                    else {stopbits = StopBits::One;}
+                
+                    // initialize the capture
+                    let mut capture = OscilloscopeCapture::new(
+                        &config.port_name.as_str(),
+                        config.baudrate,
+                        stopbits,
+                        config.data_bits,
+                        config.parity
+                    );
+                    capture.record_length = config.record_length;
+                    capture.sample_format = config.sample_format;
+                    capture.ri_read_attempts = config.ri_read_attempts;
+                    capture.ri_header_length = config.ri_header_length;
+                    capture.delays = config.delays;
+
+                    // handle commands 
+                    if config.do_capture {
+                        // create an empty response object
+                        let mut response = ScopeResponse{
+                            s1_result: false,
+                            capture_conditions: String::new(),
+                            waveform_data: Vec::<f64>::with_capacity(1000),
+                            raw_data: Vec::new(),
+                            time_per_div: ValueUnitPair::default(),
+                            voltage_per_div: ValueUnitPair::default(),
+                            diagnostics: None,
+                            extra_traces: Vec::new()
+                        };
+                    
+                        if config.do_wi {
+                            send_status(ScopeStatus::Wi);
+
+                            // the scope needs a successful S1 before it accepts the data
+                            let result = capture.send_s1().and_then(|_| {
+                                sleep(Duration::from_millis(500));
+                                capture.send_wi(config.wi_channel, &config.wi_data)
+                            });
+                            if let Ok(mut state) = shared_guard.lock() {
+                                state.link_stats.take_bytes(&mut capture);
+                                if let Err(e) = &result {
+                                    state.link_stats.count_failure(e);
+                                }
+                            }
+
+                            match result {
+                                Ok(_) => {send_status(ScopeStatus::WiSuccess);},
+                                Err(e) => {
+                                    eprintln!("Wi Failed: {e}");
+                                    send_status(ScopeStatus::WiFail);
+                                }
+                            }
+                            // an empty response tells the main thread that we are done
+                            send_response(response, config.queue_depth);
+                        }
+                        else if config.do_diag {
+                            send_status(ScopeStatus::Diag);
+                            let report = capture.diagnose();
+                            if let Ok(mut state) = shared_guard.lock() {
+                                state.link_stats.take_bytes(&mut capture);
+                            }
+
+                            if report.all_passed() {send_status(ScopeStatus::DiagSuccess);}
+                            else {send_status(ScopeStatus::DiagFail);}
 
-                                // send status message to main thread
-                                status_tx.send(ScopeStatus::RiSuccess).unwrap();
-                            },
-                            // doing the error handling inside the capture thread allows us to use the status channel to display the current status more accurately
-                            Err(e) => {
-                                let message = match e {
-                                    // at 9600 Baud every command fails a lot, so we will just retry until it succeeds, i guess
-                                    OscilloscopeError::S1Failure => {ScopeStatus::S1Fail},
-                                    OscilloscopeError::RiError => {ScopeStatus::RiFail},
-                                    OscilloscopeError::RoError => {ScopeStatus::RoFail},
-                                    OscilloscopeError::WriteError => {ScopeStatus::UnknownError},
-                                };
-                                sleep(Duration::from_millis(1000));
-                                status_tx.send(message).unwrap();
+                            response.diagnostics = Some(report);
+                            send_response(response, config.queue_depth);
+                        }
+                        else if config.do_s1 {
+                            let result = capture.send_s1();
+                            if let Ok(mut state) = shared_guard.lock() {
+                                state.link_stats.take_bytes(&mut capture);
+                            }
+                            if let Err(e) = result {
+                                eprintln!("S1 Failed: {e}");
+                                if watchdog_tripped(&mut silent_commands, &e) {
+                                    send_status(ScopeStatus::NoResponse);
+                                    continue 'thread_loop;
+                                }
+                                response.s1_result = false;
+                                // Send S1 failure status message
+                                send_status(ScopeStatus::S1Fail);
+                                send_status(ScopeStatus::Resyncing);
+                                capture.resync();
+                                send_response(response, config.queue_depth);
                                 continue 'thread_loop;
                             }
+                            silent_commands = 0;
+                            send_status(ScopeStatus::S1Success);
+                            response.s1_result = true;
+                            continue 'thread_loop;                        
+                        }
+                        else if config.do_ri {
+                            send_status(ScopeStatus::Ri);
+
+                            // the selected channel always comes first, it is the main trace
+                            // every channel takes a whole S1, Ro, Ri cycle, so only the enabled ones are polled
+                            let mut channels = vec![config.channel];
+                            channels.extend(CHANNELS.into_iter().filter(|channel| *channel != config.channel && config.acquire_channels[channel.index()]));
+
+                            let result = match config.deep_record {
+                                // a deep record already takes several transfers, so it only reads the selected channel
+                                Some(total_points) => capture.get_deep_waveform_with_progress(config.channel, total_points, |block, blocks| send_status(ScopeStatus::RiBlock(block, blocks)))
+                                    .and_then(|waveform_data| {
+                                        let (time_per_div, voltage_per_div) = processing::get_scale_units(capture.condition_string()).map_err(|_| OscilloscopeError::RoError)?;
+                                        Ok(vec![ChannelCapture {
+                                            channel: config.channel,
+                                            waveform_data,
+                                            raw_data: capture.deep_raw_samples().to_vec(),
+                                            time_per_div,
+                                            voltage_per_div,
+                                            capture_conditions: capture.condition_string().clone()
+                                        }])
+                                    }),
+                                None => capture.get_all_waveforms(&channels)
+                            };
+                            if let Ok(mut state) = shared_guard.lock() {
+                                let stats = &mut state.link_stats;
+                                stats.take_bytes(&mut capture);
+                                stats.captures_attempted += 1;
+                                match &result {
+                                    Ok(_) => stats.captures_succeeded += 1,
+                                    Err(e) => stats.count_failure(e)
+                                }
+                            }
+                            match result {
+                                Ok(mut captures) => {
+                                    // calibrated right away, so every measurement and readout sees the trimmed values
+                                    for channel_capture in captures.iter_mut() {
+                                        if let Some(threshold) = config.despike_sigma {
+                                            channel_capture.waveform_data = processing::despike(&channel_capture.waveform_data, threshold);
+                                        }
+                                        processing::apply_calibration(&mut channel_capture.waveform_data, &config.calibration[channel_capture.channel.index()]);
+                                    }
+                                    let main = captures.remove(0);
+                                    response.voltage_per_div = main.voltage_per_div;
+                                    response.time_per_div = main.time_per_div;
+                                    response.waveform_data = main.waveform_data;
+                                    response.raw_data = main.raw_data;
+                                    response.capture_conditions = main.capture_conditions;
+                                    response.extra_traces = captures;
+                                    silent_commands = 0;
+                                    failed_captures = 0;
+
+                                    // send status message to main thread
+                                    send_status(ScopeStatus::RiSuccess);
+                                },
+                                // doing the error handling inside the capture thread allows us to use the status channel to display the current status more accurately
+                                Err(e) => {
+                                    let message = match e {
+                                        // at 9600 Baud every command fails a lot, so we will just retry until it succeeds, i guess
+                                        OscilloscopeError::S1Failure => {ScopeStatus::S1Fail},
+                                        OscilloscopeError::RiError => {ScopeStatus::RiFail},
+                                        OscilloscopeError::RoError => {ScopeStatus::RoFail},
+                                        OscilloscopeError::EmptyChannel => {ScopeStatus::EmptyChannel},
+                                        OscilloscopeError::WriteError | OscilloscopeError::CarriageReturnInData => {ScopeStatus::UnknownError},
+                                        OscilloscopeError::LengthMismatch{..} => {ScopeStatus::RiFail},
+                                        OscilloscopeError::HeaderMismatch => {ScopeStatus::RiFail},
+                                        OscilloscopeError::NoResponse => {ScopeStatus::RiFail},
+                                    };
+                                    // retrying won't help if the scope doesn't answer at all
+                                    if watchdog_tripped(&mut silent_commands, &e) {
+                                        send_status(ScopeStatus::NoResponse);
+                                        continue 'thread_loop;
+                                    }
+                                    failed_captures += 1;
+                                    if config.max_retries > 0 && failed_captures > config.max_retries {
+                                        failed_captures = 0;
+                                        send_status(ScopeStatus::RetriesExhausted);
+                                        continue 'thread_loop;
+                                    }
+                                    let resync = !matches!(e, OscilloscopeError::EmptyChannel);
+                                    sleep(Duration::from_millis(1000));
+                                    send_status(message);
+                                    // whatever the scope still sends after a failed command would end up in the next response
+                                    if resync {
+                                        send_status(ScopeStatus::Resyncing);
+                                        capture.resync();
+                                    }
+                                    continue 'thread_loop;
+                                }
+                            }
+                            // send the response object back to the main frame through the response queue
+                            send_response(response, config.queue_depth);
+                            last_capture_done = Some(Instant::now());
+                        }
+                        else if config.do_ro {
+                            // only read what the scope is set to, without transferring the samples
+                            send_status(ScopeStatus::Ro);
+                            let result = capture.send_s1().and_then(|_| {
+                                sleep(Duration::from_millis(500));
+                                capture.send_ro(config.channel)
+                            });
+                            if let Ok(mut state) = shared_guard.lock() {
+                                state.link_stats.take_bytes(&mut capture);
+                            }
+                            match result {
+                                Ok(_) => {
+                                    silent_commands = 0;
+                                    response.s1_result = true;
+                                    response.capture_conditions = capture.condition_string().clone();
+                                    send_status(ScopeStatus::RoSuccess);
+                                },
+                                Err(e) => {
+                                    eprintln!("Ro Failed: {e}");
+                                    if watchdog_tripped(&mut silent_commands, &e) {
+                                        send_status(ScopeStatus::NoResponse);
+                                    }
+                                    send_status(ScopeStatus::RoFail);
+                                    send_status(ScopeStatus::Resyncing);
+                                    capture.resync();
+                                }
+                            }
+                            // an empty condition string tells the main thread that it failed
+                            send_response(response, config.queue_depth);
                         }
-                        // send the response object back to the main frame through the waveform_tx channel
-                        waveform_tx.send(response).unwrap();
-                    }
-                    else if config.do_ro {
-                        println!("{:?}",capture.send_ro(config.channel));
-                        // TODO? Idk this seems kinda useless
-                        response.s1_result = true;
-                        response.capture_conditions = String::from("TODO");
-                        waveform_tx.send(response).unwrap()
                     }
-                }
-                //sleep(Duration::from_millis(500));
+                    //sleep(Duration::from_millis(500));
 
 
+                }
+                // if we don't have to do anything, take a nap
+                else {
+                    // the next periodic capture starts right away
+                    last_capture_done = None;
+                    sleep(Duration::from_millis(1000));
+                    send_status(ScopeStatus::Idle);
+                    continue 'thread_loop;
+                }
+            }
+        };
+        loop {
+            let Err(panic) = std::panic::catch_unwind(AssertUnwindSafe(&mut capture_loop)) else {
+                break;
+            };
+            // the lock may have been held when it happened, the state behind it is still usable
+            shared_guard.clear_poison();
+            config_mutex_guard.clear_poison();
+            if let Ok(mut state) = shared_guard.lock() {
+                state.statuses.push_back(ScopeStatus::InternalError(panic_message(&panic)));
             }
-            // if we don't have to do anything, take a nap
-            else {
-                sleep(Duration::from_millis(1000));
-                status_tx.send(ScopeStatus::Idle).unwrap();
-                continue 'thread_loop;
+            if shutdown_guard.load(Ordering::SeqCst) {
+                break;
             }
+            // start over with a fresh port, after a moment so a panic on every capture doesn't spin
+            sleep(CAPTURE_RESTART_DELAY);
         }
     });
 
     support::simple_init("scope-ui", move |_, ui| {
         let display_size = ui.io().display_size;
-        let (mouse_x, _mouse_y) = ui.io().mouse_pos.into();
+        // with the trigger point shown, times count from it like on a scope, negative before the trigger
+        let time_zero = trigger_sample.filter(|_| show_trigger_point).unwrap_or(0);
+        // the interpolator settings take the rightmost column, draw and capture controls the one left of it
+        let column_width = control_column_width(display_size[0]);
+        let controls_x = (display_size[0] - 2.0 * column_width).max(0.0);
+        let layout_condition = if lock_layout {Condition::Always} else {Condition::FirstUseEver};
+        let layout_flags = if lock_layout {WindowFlags::NO_DECORATION | WindowFlags::NO_MOVE} else {WindowFlags::empty()};
+        let (mouse_x, mouse_y) = ui.io().mouse_pos.into();
 
         //println!("{:?}", current_config.port_name);
         ui.window("Main Window, I guess?")
@@ -326,31 +1734,82 @@ fn main() {
             .build(|| {
                 // empty window, for now
                 ui.invisible_button("main_invis", [1.0,395.0]);
-                if let Ok(a) = status_rx.try_recv() {
+                let status = shared.lock().ok().and_then(|mut state| state.statuses.pop_front());
+                if let Some(a) = status {
+                    // the shown trace no longer matches what the scope has
+                    if matches!(a, ScopeStatus::S1Fail | ScopeStatus::RoFail | ScopeStatus::RiFail | ScopeStatus::EmptyChannel | ScopeStatus::NoResponse) && last_good_capture.is_some() {
+                        trace_stale = true;
+                        if clear_on_failure {
+                            waveform_buffer = vec![0.0f64; waveform_buffer.len().max(MIN_WAVEFORM_SAMPLES)];
+                            raw_buffer.clear();
+                        }
+                    }
+                    deep_progress = if let ScopeStatus::RiBlock(block, blocks) = a {Some((block, blocks))} else {None};
                     status_string = match a {
                          ScopeStatus::Idle => "Idle",
                          ScopeStatus::Ri => "Getting Waveform",
                          ScopeStatus::RiFail => "Failed to get Waveform",
                          ScopeStatus::RiSuccess => "Waveform captured",
+                         ScopeStatus::RiBlock(..) => "Getting Deep Record",
                          ScopeStatus::S1 => "Testing Connection",
+                         ScopeStatus::Ro => "Getting Measurement Conditions",
                          ScopeStatus::RoFail => "Failed to get Measurement Conditions",
+                         ScopeStatus::RoSuccess => "Measurement Conditions received",
+                         ScopeStatus::EmptyChannel => "Channel is empty",
                          ScopeStatus::S1Fail => "Connection Failed",
                          ScopeStatus::S1Success => "Connection Successful",
+                         ScopeStatus::Wi => "Storing Waveform",
+                         ScopeStatus::WiFail => "Failed to store Waveform",
+                         ScopeStatus::WiSuccess => "Waveform stored",
+                         ScopeStatus::Diag => "Running Diagnostics",
+                         ScopeStatus::DiagFail => "Diagnostics found a problem",
+                         ScopeStatus::DiagSuccess => "Diagnostics passed",
+                         ScopeStatus::Resyncing => "Resynchronizing",
+                         ScopeStatus::NoResponse => "No response from the scope",
+                         ScopeStatus::RetriesExhausted => "Capture failed, out of retries",
+                         ScopeStatus::InternalError(_) => "Capture thread crashed, restarted",
                          _ => "undefined"
                     };
+                    // stop hammering a scope that doesn't answer, until the user tries again
+                    if matches!(a, ScopeStatus::NoResponse) {
+                        no_response = true;
+                        do_capture = false;
+                    }
+                    else if matches!(a, ScopeStatus::RetriesExhausted) {
+                        do_capture = false;
+                    }
+                    // whatever made it panic would most likely do it again on the next capture
+                    else if let ScopeStatus::InternalError(message) = &a {
+                        internal_error = Some(message.clone());
+                        do_capture = false;
+                    }
+                    else if matches!(a, ScopeStatus::S1Success | ScopeStatus::RoSuccess | ScopeStatus::RiSuccess | ScopeStatus::WiSuccess) {
+                        no_response = false;
+                        internal_error = None;
+                    }
                 }
                 ui.columns(5, "main_cols", false);
                 ui.text(status_string);
+                if let Some((block, blocks)) = deep_progress {
+                    ProgressBar::new(block as f32 / blocks.max(1) as f32).overlay_text(format!("Block {} of {}", (block + 1).min(blocks), blocks)).build(&ui);
+                }
+                if let Some(message) = &internal_error {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], format!("Internal error: {message}"));
+                }
+                if no_response {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], "Check that the scope is powered and in remote (GP-IB) mode");
+                }
+                if let Some(message) = startup_message.as_ref().filter(|_| !do_capture) {
+                    ui.text_colored([1.0, 0.8, 0.2, 1.0], message);
+                }
                 
             });
         
         ui.window("Interpolator Settings")
-            .position([800.0,0.0], Condition::Always)
-            .size([300.0,400.0], Condition::Always)
-            .no_decoration()
-            .movable(false)
+            .flags(layout_flags)
+            .position([controls_x + column_width, 0.0], layout_condition)
+            .size([column_width, display_size[1]], layout_condition)
             .collapsible(false)
-            .resizable(false)
             .build(|| {
                 ui.text("Interpolation Method");
                 ui.columns(2, "interp_method", false);
@@ -360,58 +1819,455 @@ fn main() {
                 ui.radio_button("Catmull-Rom", &mut interpolation_method, 2);
                 ui.radio_button("Bézier", &mut interpolation_method, 3);
                 ui.radio_button("Bézier Variant", &mut interpolation_method, 4);
+                ui.radio_button("Exact Nodes", &mut interpolation_method, EXACT_NODES_METHOD);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Goes through every captured sample, the samples are rounded to a multiple of the capture length");
+                }
                 ui.columns(1, "interp_samples", false);
                 ui.separator();
-                ui.slider("Samples", 1001, 16000, &mut interpol_samples);
+                precise_slider(&ui, "Samples", INTERPOL_SAMPLES_RANGE.0, INTERPOL_SAMPLES_RANGE.1, &mut interpol_samples);
                 ui.slider("Step", 1, 20, &mut interpol_step);
                 ui.separator();
-                ui.slider("Samples 2", 1001, u16::MAX as usize, &mut interpol2_samples);
+                ui.disabled(interpol2_auto, || {
+                    precise_slider(&ui, "Samples 2", INTERPOL2_SAMPLES_RANGE.0, INTERPOL2_SAMPLES_RANGE.1, &mut interpol2_samples);
+                });
+                ui.same_line();
+                if ui.checkbox("Auto", &mut interpol2_auto) && interpol2_auto {
+                    interpol2_samples = auto_interpol2_samples(waveform_buffer.len());
+                }
                 ui.slider("Step 2", 1, 50, &mut interpol2_step);
+                if auto_sample_cap && sample_cap < 1.0 {
+                    ui.text_colored([1.0, 1.0, 0.0, 1.0], format!("Sample cap active: {:.0}%", sample_cap * 100.0));
+                }
                 ui.separator();
                 ui.slider("Window Size", 1, max_window_size, &mut avg_window_size);
+                ui.separator();
+                ui.combo_simple_string("Filter", &mut filter_method, &FILTER_METHODS);
+                ui.disabled(filter_method != 1, || {
+                    if ui.slider("Filter Window", SAVGOL_WINDOW_RANGE.0, SAVGOL_WINDOW_RANGE.1, &mut savgol_window) && savgol_window % 2 == 0 {
+                        savgol_window += 1;
+                    }
+                    ui.slider("Filter Order", SAVGOL_ORDER_RANGE.0, SAVGOL_ORDER_RANGE.1, &mut savgol_order);
+                    savgol_order = savgol_order.min(savgol_window - 2);
+                });
+                ui.checkbox("Remove Hum", &mut remove_hum);
+                ui.disabled(!remove_hum, || {
+                    ui.same_line();
+                    ui.set_next_item_width(80.0);
+                    ui.combo_simple_string("##mains_frequency", &mut mains_frequency, &MAINS_FREQUENCIES);
+                    ui.slider("Harmonics", 0, 5, &mut hum_harmonics);
+                });
+                // see what the filters do over the unfiltered trace before drawing with them
+                ui.checkbox("Preview Filters", &mut filter_preview);
+                ui.same_line();
+                ui.disabled(!filter_preview, || {
+                    if ui.button("Apply") {
+                        filter_preview = false;
+                    }
+                });
+                ui.separator();
+                ui.checkbox("Compare Methods", &mut compare_methods);
+                ui.disabled(!compare_methods, || {
+                    ui.combo_simple_string("Compare With", &mut compare_method, &INTERPOLATION_METHODS);
+                });
+                ui.checkbox("Show Residual", &mut show_residual);
+                ui.disabled(!show_residual, || {
+                    ui.slider("Residual Gain", 1.0, 100.0, &mut residual_gain);
+                });
             });
         
         ui.window("Draw Controls")
-            .size([300.0,200.0], Condition::Always)
-            .position([500.0,0.0], Condition::Always)
+            .flags(layout_flags)
+            .size([column_width, display_size[1] / 2.0], layout_condition)
+            .position([controls_x, 0.0], layout_condition)
             .collapsible(false)
-            .no_decoration()
-            .resizable(false)
-            .movable(false)
-            .no_decoration()
             .build(|| {
                 ui.columns(2, "Draw Control Columns", false);
-                ui.set_column_width(0, 152.0);
+                ui.set_column_width(0, ui.window_size()[0] * 0.5);
                 ui.checkbox("Draw Moving Average", &mut draw_average);
                 ui.checkbox("Draw Trace", &mut draw_main_trace);
                 ui.checkbox("Draw Grid", &mut draw_grid);
-                ui.checkbox("Snap to trace", &mut snap_to_trace);
                 ui.checkbox("Draw Dots", &mut draw_dots);
+                ui.checkbox("Fill to Ground", &mut draw_fill);
+                ui.checkbox("Difference", &mut draw_difference);
+                ui.checkbox("Raw Stems", &mut draw_stems);
+                ui.checkbox("Sample and Hold", &mut draw_hold);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Draw the captured samples as steps instead of the interpolated trace");
+                }
+                ui.checkbox("Eye Diagram", &mut show_eye);
+                ui.checkbox("Spectrogram", &mut show_spectrogram);
+                ui.checkbox("Ideal Waveform", &mut show_ideal);
+                ui.checkbox("Measurements", &mut show_measurements);
+                ui.checkbox("Counter", &mut show_counter);
+                ui.checkbox("Measurement Table", &mut show_measurement_table);
+                ui.checkbox("Capture Statistics", &mut show_capture_stats);
+                ui.checkbox("Performance", &mut show_performance);
+                ui.checkbox("Link Stats", &mut show_link_stats);
+                ui.checkbox("Highlight Clipping", &mut highlight_clipping);
+                ui.checkbox("Mark Peaks", &mut mark_peaks);
+                ui.checkbox("Magnifier", &mut show_magnifier);
+                ui.checkbox("Lock Layout", &mut lock_layout);
+                if ui.checkbox("Afterglow", &mut draw_afterglow) && !draw_afterglow {
+                    afterglow.clear();
+                }
+                ui.disabled(!draw_afterglow, || {
+                    ui.set_next_item_width(100.0);
+                    ui.slider("Afterglow Traces", 1, 32, &mut afterglow_traces);
+                    ui.set_next_item_width(100.0);
+                    ui.combo_simple_string("Fade", &mut afterglow_curve, &AFTERGLOW_CURVES);
+                    // the afterglow is only as bright as the grid, so both sit equally far in the background
+                    ui.checkbox("Match Grid Opacity", &mut afterglow_grid_opacity);
+                });
+                if ui.checkbox("Heatmap", &mut draw_heatmap) && !draw_heatmap {
+                    heatmap.clear();
+                }
+                ui.disabled(!draw_heatmap, || {
+                    ui.set_next_item_width(100.0);
+                    ui.slider("Heatmap Traces", 1, 500, &mut heatmap_traces);
+                });
                 ui.next_column();
                 ui.text("Trace Thickness");
-                ui.slider(" ", 1.0, 5.0, &mut trace_thickness);
+                precise_slider(&ui, " ", THICKNESS_RANGE.0, THICKNESS_RANGE.1, &mut trace_thickness);
                 ui.text("Moving Average Thickness");
-                ui.slider("   ", 1.0, 5.0, &mut avg_thickness);
+                precise_slider(&ui, "   ", THICKNESS_RANGE.0, THICKNESS_RANGE.1, &mut avg_thickness);
                 ui.text("Grid Opacity");
                 ui.slider("    ", 1, 255, &mut grid_opacity);
+                ui.text("Grid Thickness");
+                ui.slider("##grid_thickness", 1.0, 4.0, &mut grid_thickness);
+                ui.text("Fill Opacity");
+                ui.disabled(!draw_fill, || {
+                    ui.slider("     ", 0.0, 1.0, &mut fill_alpha);
+                });
+                ui.checkbox("Anti-aliased Trace", &mut trace_antialiasing);
                 ui.columns(1, "Draw Control Columns 2", false);
                 ui.separator();
-                ui.slider("X Scale", 0.1, 10.0, &mut x_scale);
-                ui.slider("Y Scale", 0.1, 10.0, &mut y_scale);
+                precise_slider(&ui, "X Scale", SCALE_RANGE.0, SCALE_RANGE.1, &mut x_scale);
+                ui.same_line();
+                ui.checkbox("Smooth Zoom", &mut smooth_zoom);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Keep the middle of the screen in place while scrolling to zoom, down to a fraction of a sample");
+                }
+                ui.set_next_item_width(80.0);
+                ui.slider("Pan Speed", 0.01, 1.0, &mut scroll_pan_speed);
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Scroll to pan, Ctrl+Scroll to zoom horizontally, Shift+Scroll to zoom vertically");
+                }
+                ui.same_line();
+                ui.set_next_item_width(80.0);
+                ui.slider("Zoom Speed", 0.01, 1.0, &mut scroll_zoom_speed);
+                // exact positioning, dragging is too coarse for lining up screenshots
+                let last_display_index = display_frame.interp_data.len().saturating_sub(1);
+                if ui.arrow_button("position_left", Direction::Left) {
+                    index_start = index_start.saturating_sub(1);
+                }
+                ui.same_line();
+                if ui.arrow_button("position_right", Direction::Right) {
+                    index_start = (index_start + 1).min(last_display_index);
+                }
+                ui.same_line();
+                ui.set_next_item_width(120.0);
+                ui.slider("Position", 0, last_display_index, &mut index_start);
+                ui.same_line();
+                if ui.button("Center Trigger") {
+                    trigger_sample = trigger_index(&waveform_buffer, &trigger);
+                    trigger_position = 0.5;
+                    align_trigger = true;
+                }
+                precise_slider(&ui, "Y Scale", SCALE_RANGE.0, SCALE_RANGE.1, &mut channel_display[channel.index()].y_scale);
+                ui.text("Snap Cursor");
+                ui.same_line();
+                ui.radio_button("Off", &mut snap_mode, SnapMode::None);
+                ui.same_line();
+                ui.radio_button("Trace", &mut snap_mode, SnapMode::Trace);
+                ui.same_line();
+                ui.radio_button("Grid", &mut snap_mode, SnapMode::Grid);
+                ui.checkbox("Hover Tooltip", &mut hover_tooltip);
+                ui.checkbox("Time in Divisions", &mut time_in_divisions);
                 if CollapsingHeader::new("Trace Colors")
                     .default_open(false)
                     .build(&ui) {
-                        ui.columns(2, "Colors", true);
+                        ui.columns(6, "Colors", true);
                         ui.color_picker4("Main Trace", &mut trace_color);
                         ui.next_column();
                         ui.color_picker4("Moving Average ", &mut avg_color);
+                        ui.next_column();
+                        ui.color_picker4("Comparison", &mut compare_color);
+                        ui.next_column();
+                        ui.color_picker4("Reference", &mut reference_color);
+                        ui.next_column();
+                        ui.color_picker4("Difference", &mut difference_color);
+                        ui.next_column();
+                        ui.color_picker4("Background", &mut background_color);
+                }
+                ui.columns(1, "Reference Columns", false);
+                if CollapsingHeader::new("Reference")
+                    .default_open(false)
+                    .build(&ui) {
+                        // keep a copy of the current capture to compare later captures against
+                        if ui.button("Store Reference") {
+                            reference_buffer = waveform_buffer.clone();
+                            reference_channel = channel;
+                            reference_time_per_div = time_per_div.clone();
+                        }
+                        ui.same_line();
+                        if ui.button("Clear Reference") {
+                            reference_buffer.clear();
+                        }
+                        ui.checkbox("Draw Reference", &mut draw_reference);
+
+                        // load a (time, voltage) CSV, e.g. from a simulation, as the reference
+                        ui.input_text("CSV File", &mut import_path).build();
+                        if ui.button("Import CSV") {
+                            import_message = match std::fs::read_to_string(&import_path).map_err(|_| ()).and_then(|text| os3000::csv::parse_csv(&text)) {
+                                Ok(points) => {
+                                    // without a capture, use the span of the imported data as the time base
+                                    let (seconds_per_div, unit_mult) = if time_per_div.unit_mult > 0.0 && time_per_div.value > 0.0 {
+                                        (time_per_div.value / time_per_div.unit_mult, voltage_per_div.unit_mult.max(1.0))
+                                    }
+                                    else {
+                                        ((points[points.len() - 1].0 - points[0].0) / 10.0, 1.0)
+                                    };
+                                    let (samples, overlaps) = os3000::csv::resample_points(&points, seconds_per_div, waveform_buffer.len());
+                                    // stored upside down and in the unit of the live capture, like the captured samples
+                                    reference_buffer = samples.iter().map(|volts| -volts * unit_mult).collect();
+                                    reference_time_per_div = ValueUnitPair{value: seconds_per_div, unit_mult: 1.0, unit_name: String::from("s")};
+                                    if overlaps {format!("Imported {} points", points.len())}
+                                    else {String::from("Warning: imported data doesn't overlap the capture")}
+                                },
+                                Err(_) => String::from("Failed to import CSV")
+                            };
+                        }
+                        ui.text_wrapped(&import_message);
+                }
+                if CollapsingHeader::new("Export")
+                    .default_open(false)
+                    .build(&ui) {
+                        // the capture goes to the given file, its measurements to a companion file next to it
+                        ui.input_text("Export File", &mut export_path).build();
+                        ui.combo_simple_string("Time Column", &mut export_time_column, &EXPORT_TIME_COLUMNS);
+                        let delimiter_names = EXPORT_DELIMITERS.map(|(name, _)| name);
+                        ui.combo_simple_string("Delimiter", &mut export_delimiter, &delimiter_names);
+                        ui.checkbox("Limit Decimals", &mut export_limit_decimals);
+                        if export_limit_decimals {
+                            ui.slider("Decimals", 0, 15, &mut export_decimals);
+                        }
+                        if ui.button("Export CSV") {
+                            let time_column = match export_time_column {
+                                0 if time_in_divisions => TimeColumn::Divisions,
+                                2 => TimeColumn::Samples,
+                                3 => TimeColumn::Divisions,
+                                _ => TimeColumn::Seconds
+                            };
+                            let options = CsvOptions {time_column, delimiter: EXPORT_DELIMITERS[export_delimiter].1, precision: if export_limit_decimals {Some(export_decimals)} else {None}};
+                            let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                            let measurements = gated_measurements(&waveform_buffer, &raw_buffer, &time_per_div, &voltage_per_div, &Measurement::ALL, reference_levels);
+                            let measurements_path = std::path::Path::new(&export_path).with_extension("measurements.csv");
+                            let result = std::fs::write(&export_path, os3000::csv::format_waveform(&waveform_buffer, seconds_per_div, &voltage_per_div.unit_name, &options, time_zero))
+                                .and_then(|_| std::fs::write(&measurements_path, os3000::csv::format_measurements(&measurements, &options)));
+                            export_message = match result {
+                                Ok(_) => format!("Exported to {} and {}", export_path, measurements_path.display()),
+                                Err(e) => format!("Failed to export: {e}")
+                            };
+                        }
+                        ui.input_int2("SVG Size", &mut svg_size).build();
+                        if ui.button("Export SVG") {
+                            if let Some(view) = export_view {
+                                let ChannelDisplay {y_offset, y_scale} = placed_display(&channel_display, channel, lock_baseline);
+                                let reference_display = placed_display(&channel_display, reference_channel, lock_baseline);
+                                let rgba = |color: Vector4<f32>| [color.x, color.y, color.z, color.w];
+                                let mut traces: Vec<SvgTrace> = Vec::new();
+                                if draw_main_trace {
+                                    traces.push(SvgTrace {samples: &display_frame.interp_data, color: rgba(trace_color), thickness: trace_thickness, y_offset, pixels_per_unit: pixels_per_unit(&voltage_per_div, view.height, y_scale)});
+                                }
+                                if let Some(reference_data) = &display_frame.reference_data {
+                                    traces.push(SvgTrace {samples: reference_data, color: rgba(reference_color), thickness: trace_thickness, y_offset: reference_display.y_offset, pixels_per_unit: pixels_per_unit(&voltage_per_div, view.height, reference_display.y_scale)});
+                                }
+                                let measurements = gated_measurements(&waveform_buffer, &raw_buffer, &time_per_div, &voltage_per_div, &Measurement::ALL, reference_levels);
+                                let annotations: Vec<String> = measurements_clipboard_text(&measurements, &voltage_per_div).lines().map(String::from).collect();
+                                let scale_label = format!("{}{}/div, {}{}/div", voltage_per_div.value, voltage_per_div.unit_name, time_per_div.value, time_per_div.unit_name);
+                                let svg_path = std::path::Path::new(&export_path).with_extension("svg");
+                                let size = (svg_size[0].max(1) as u32, svg_size[1].max(1) as u32);
+                                export_message = match std::fs::write(&svg_path, svg::format_view(&view, &traces, &scale_label, &annotations, size)) {
+                                    Ok(_) => format!("Exported to {}", svg_path.display()),
+                                    Err(e) => format!("Failed to export: {e}")
+                                };
+                            }
+                        }
+                        ui.text_wrapped(&export_message);
+                }
+                if CollapsingHeader::new("Recording")
+                    .default_open(false)
+                    .build(&ui) {
+                        ui.input_text("Recording File", &mut record_path).build();
+                        if ui.checkbox("Record Captures", &mut recording) && recording {
+                            recorded_captures = 0;
+                            record_message.clear();
+                        }
+                        if recording {
+                            ui.same_line();
+                            ui.text(format!("{} recorded", recorded_captures));
+                        }
+                        if ui.button("Load Recording") {
+                            record_message = match std::fs::read(&record_path).map_err(|_| ()).and_then(|bytes| format::read_captures(&bytes)) {
+                                Ok(records) if !records.is_empty() => {
+                                    let message = format!("Loaded {} captures, live captures aren't shown during the replay", records.len());
+                                    replay = records;
+                                    replay_position = 0;
+                                    replay_shown = None;
+                                    replay_playing = false;
+                                    message
+                                },
+                                Ok(_) => String::from("The recording is empty"),
+                                Err(_) => String::from("Failed to load the recording")
+                            };
+                        }
+                        if !replay.is_empty() {
+                            ui.same_line();
+                            if ui.button("Stop Replay") {
+                                replay.clear();
+                                replay_playing = false;
+                            }
+                        }
+                        if !replay.is_empty() {
+                            // the timeline, dragging it shows every capture on the way
+                            ui.slider("Capture", 0, replay.len() - 1, &mut replay_position);
+                            if ui.button(if replay_playing {"Pause"} else {"Play"}) {
+                                replay_playing = !replay_playing;
+                                last_replay_step = Instant::now();
+                            }
+                            ui.same_line();
+                            ui.set_next_item_width(120.0);
+                            ui.slider("Captures/s", 0.5, 20.0, &mut replay_rate);
+                        }
+                        ui.text_wrapped(&record_message);
+                }
+                if CollapsingHeader::new("Scrollback")
+                    .default_open(false)
+                    .build(&ui) {
+                        if ui.slider("Keep Captures", 1, 500, &mut scrollback_length) {
+                            while scrollback.len() > scrollback_length {
+                                scrollback.pop_front();
+                                scrollback_view = scrollback_view.map(|position| position.saturating_sub(1));
+                                scrollback_shown = None;
+                            }
+                        }
+                        if scrollback.is_empty() {
+                            ui.text("No captures yet");
+                        }
+                        else {
+                            // the newest capture is at the right end, like on a timeline
+                            let mut position = scrollback_view.unwrap_or(scrollback.len() - 1);
+                            if ui.slider("Capture##scrollback", 0, scrollback.len() - 1, &mut position) {
+                                scrollback_view = Some(position);
+                            }
+                            let age = scrollback[position].0.elapsed().as_secs_f32();
+                            ui.text(format!("{} of {}, {:.1}s ago", position + 1, scrollback.len(), age));
+                            if scrollback_view.is_some() {
+                                ui.same_line();
+                                if ui.button("Live") {
+                                    scrollback_view = None;
+                                    scrollback_shown = None;
+                                    // show the newest capture right away instead of waiting for the next one
+                                    if let Some((_, record)) = scrollback.back() {
+                                        waveform_buffer = record.samples.clone();
+                                        time_per_div = record.time_per_div.clone();
+                                        voltage_per_div = record.voltage_per_div.clone();
+                                        raw_buffer.clear();
+                                        settings_changed = false;
+                                    }
+                                }
+                                ui.text_colored([1.0, 0.8, 0.2, 1.0], "Live captures are kept but not shown");
+                            }
+                        }
                 }
             }
         );
         
 
+        // the replay takes the place of the live capture
+        if !replay.is_empty() {
+            if replay_playing && last_replay_step.elapsed().as_secs_f32() >= 1.0 / replay_rate {
+                last_replay_step = Instant::now();
+                replay_position += 1;
+                // stop at the end instead of looping, the last capture stays on screen
+                if replay_position >= replay.len() - 1 {
+                    replay_position = replay.len() - 1;
+                    replay_playing = false;
+                }
+            }
+            if replay_shown != Some(replay_position) {
+                let record = &replay[replay_position];
+                waveform_buffer = record.samples.clone();
+                time_per_div = record.time_per_div.clone();
+                voltage_per_div = record.voltage_per_div.clone();
+                // there are no raw bytes in a recording, so nothing to check for clipping either
+                raw_buffer.clear();
+                settings_changed = false;
+                replay_shown = Some(replay_position);
+            }
+        }
+
+        // an older capture from the scrollback takes the place of the live one
+        if let Some(position) = scrollback_view {
+            if scrollback_shown != Some(position) {
+                if let Some((_, record)) = scrollback.get(position) {
+                    waveform_buffer = record.samples.clone();
+                    time_per_div = record.time_per_div.clone();
+                    voltage_per_div = record.voltage_per_div.clone();
+                    // like in a recording, the raw bytes aren't kept
+                    raw_buffer.clear();
+                    settings_changed = false;
+                }
+                scrollback_shown = Some(position);
+            }
+        }
+
+        // exact nodes need a whole number of drawn points per sample, otherwise most samples fall between two of them
+        let drawn_samples = if auto_sample_cap {capped_samples(interpol_samples, sample_cap, INTERPOL_SAMPLES_RANGE.0)} else {interpol_samples};
+        let drawn_samples = if interpolation_method == EXACT_NODES_METHOD {processing::node_aligned_samples(drawn_samples, waveform_buffer.len())} else {drawn_samples};
+
+        // hand the current settings to the processing thread whenever they change
+        let params = ProcessingParams {
+            waveform: waveform_buffer.clone(),
+            time_per_div: time_per_div.value,
+            interpol_samples: drawn_samples,
+            interpol_step,
+            interpol2_samples: if auto_sample_cap {capped_samples(interpol2_samples, sample_cap, INTERPOL2_SAMPLES_RANGE.0)} else {interpol2_samples},
+            interpol2_step,
+            interpolation_method,
+            filter_method,
+            savgol_window,
+            savgol_order,
+            notch_hz: if remove_hum {Some(if mains_frequency == 0 {50.0} else {60.0})} else {None},
+            notch_harmonics: hum_harmonics,
+            filter_preview,
+            sample_rate: processing::sample_rate(&time_per_div),
+            compare_method: if compare_methods {Some(compare_method as u8)} else {None},
+            show_residual,
+            reference: if draw_reference {reference_buffer.clone()} else {Vec::new()},
+            seconds_per_div: if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0},
+            reference_seconds_per_div: if reference_time_per_div.unit_mult > 0.0 {reference_time_per_div.value / reference_time_per_div.unit_mult} else {0.0},
+            extra: extra_traces.iter().map(|trace| trace.waveform_data.clone()).collect(),
+            difference: if draw_difference && !previous_buffer.is_empty() {processing::diff(&waveform_buffer, &previous_buffer)} else {Vec::new()},
+            moving_average_voltage: if draw_average {Some(voltage_per_div.value)} else {None}
+        };
+        if params != last_params {
+            // the very first frame is computed right away so there's always something to draw
+            if display_frame.interp_data.is_empty() {
+                display_frame = process_frame(&params);
+            }
+            let _ = params_tx.send(params.clone());
+            last_params = params;
+        }
+        if let Ok(mut latest) = frame_mutex.try_lock() {
+            if let Some(frame) = latest.take() {
+                display_frame = frame;
+            }
+        }
+
         ui.window("Drawing Window")
-            .size([500.0,400.0], Condition::Appearing)
+            .size([controls_x, display_size[1]], layout_condition)
             .position([0.0,0.0], Condition::Always)
             .resizable(false)
             .collapsible(false)
@@ -423,104 +2279,400 @@ fn main() {
                 let draw_list = ui.get_window_draw_list();
                 let (window_width, window_height) = ui.window_size().into();
                 let (win_x, win_y) = ui.window_pos().into();
-                let interp_data_lin = linear_interpolate_samples(&waveform_buffer, interpol2_samples, time_per_div.value, interpol2_step);
+                let interp_data_lin = &display_frame.interp_data_lin;
                 
                 max_window_size = interp_data_lin.len() / 2;
 
-                // increase samples by scrolling
-                if ui.is_window_hovered() && ui.is_window_focused() && !ui.io().key_ctrl{
-                    if ui.io().mouse_wheel < 0.0 && x_scale > 0.1 {
-                        //interpol_samples -= 100;
-                        x_scale -= 0.1;
-                        
+                // scroll pans, ctrl + scroll zooms horizontally and shift + scroll vertically
+                // some platforms turn shift + scroll into a horizontal wheel, so that counts as well
+                let wheel = if ui.io().mouse_wheel != 0.0 {ui.io().mouse_wheel} else {ui.io().mouse_wheel_h};
+                if ui.is_window_hovered() && ui.is_window_focused() && wheel != 0.0 {
+                    if ui.io().key_ctrl {
+                        let last_x_scale = x_scale;
+                        // don't let scrolling go past what the slider allows, a zero scale breaks the index math
+                        x_scale = (x_scale + wheel * scroll_zoom_speed).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+
+                        // zoom around the middle of the screen, the start can land between two samples
+                        if smooth_zoom && x_scale != last_x_scale {
+                            let center = index_start as f32 + view_fraction + window_width / 2.0 / (last_x_scale / 2.0);
+                            let view_start = (center - window_width / 2.0 / (x_scale / 2.0)).max(0.0);
+                            index_start = view_start.floor() as usize;
+                            view_fraction = view_start.fract();
+                        }
                     }
-                    
-                    else if ui.io().mouse_wheel > 0.0 {
-                        //interpol_samples += 100;
-                        x_scale += 0.1;
+                    else if ui.io().key_shift {
+                        let y_scale = &mut channel_display[channel.index()].y_scale;
+                        *y_scale = (*y_scale + wheel * scroll_zoom_speed).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+                    }
+                    else {
+                        // scrolling down moves later into the capture
+                        let last_display_index = display_frame.interp_data.len().saturating_sub(1) as f32;
+                        let view_start = (index_start as f32 + view_fraction - wheel * scroll_pan_speed * window_width / (x_scale / 2.0)).clamp(0.0, last_display_index);
+                        index_start = view_start.floor() as usize;
+                        view_fraction = view_start.fract();
                     }
                 }
+                if !smooth_zoom {
+                    view_fraction = 0.0;
+                }
+                // the traces are shifted left by the part of a sample that's scrolled past
+                let x_offset = -view_fraction * (x_scale / 2.0);
 
-                // 0 Linear
-                // 1 Cosine
-                // 2 Catmull-Rom
-                // 3 Bézier
-                // 4 Bézier variant 
-
-                let interp_data:Vec<f64> = match interpolation_method {
-                    0 => {linear_interpolate_samples(&interp_data_lin, interpol_samples, time_per_div.value, interpol_step)},
-                    1 => {cosine_interpolate_samples(&interp_data_lin, interpol_samples, time_per_div.value, interpol_step)},
-                    2 => {catmull_rom_interpolate_samples(&interp_data_lin, interpol_samples, time_per_div.value, interpol_step)},
-                    3 => {bezier_interpolate_samples(&interp_data_lin, interpol_samples, time_per_div.value, interpol_step)},
-                    4 => {bezier2_interpolate_samples(&interp_data_lin, interpol_samples, time_per_div.value, interpol_step)},
-                    _ => {interp_data_lin.clone()}
-                };
+                let interp_data: &Vec<f64> = &display_frame.interp_data;
 
                 let index_end = interp_data.len() - 1;
 
+                // put a new trigger point at the chosen fraction of the screen width
+                if align_trigger {
+                    align_trigger = false;
+                    if let Some(sample) = trigger_sample {
+                        let display_index = sample as f32 * (interp_data.len() as f32 / waveform_buffer.len() as f32);
+                        index_start = (display_index - window_width * trigger_position / (x_scale / 2.0)).max(0.0) as usize;
+                    }
+                }
+
                 // update the start index (mouse dragging moves waveform left and right)
                 update_start_index(index_end, &mut index_start, &ui, x_scale);
     
                 // draw background
-                draw_list.add_rect(ui.window_pos(), [win_x + window_width, win_y + window_height], color::ImColor32::from_rgb(10, 10, 10)).filled(true).build();
+                draw_list.add_rect(ui.window_pos(), [win_x + window_width, win_y + window_height], color::ImColor32::from_rgba_f32s(background_color.x, background_color.y, background_color.z, 1.0)).filled(true).build();
 
                 //draw grid lines
                 if draw_grid {
                     let line_color = color::ImColor32::from_rgba(244, 244, 233, grid_opacity);
-                    draw_grid_lines(line_color, 5.0, &ui, &draw_list);
+                    draw_grid_lines(line_color, 5.0, grid_thickness, &ui, &draw_list);
+                }
+
+                // everything left of the trigger point happened before t=0
+                let trigger_x = trigger_sample.filter(|_| show_trigger_point)
+                    .map(|sample| raw_index_to_x(sample, waveform_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset));
+                if let Some(x) = trigger_x {
+                    if shade_pre_trigger && x > win_x {
+                        draw_list.add_rect([win_x, win_y], [x.min(win_x + window_width), win_y + window_height], color::ImColor32::from_rgba(60, 60, 90, 80)).filled(true).build();
+                    }
+                }
+
+                // the live trace and everything derived from it use the capture channel's placement
+                let ChannelDisplay {y_offset, y_scale} = placed_display(&channel_display, channel, lock_baseline);
+                let reference_display = placed_display(&channel_display, reference_channel, lock_baseline);
+                // how tall one unit of the samples is on screen
+                let trace_pixels_per_unit = pixels_per_unit(&voltage_per_div, window_height, y_scale);
+                let reference_pixels_per_unit = pixels_per_unit(&voltage_per_div, window_height, reference_display.y_scale);
+                // every trace in the window starts from the live trace's placement
+                let trace_style = TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w), offsets: (x_offset, y_offset), scales: (x_scale / 2.0, trace_pixels_per_unit), thickness: trace_thickness};
+
+                // what the SVG export redraws
+                export_view = Some(SvgView {
+                    width: window_width,
+                    height: window_height,
+                    index_start,
+                    x_offset,
+                    x_scale: x_scale / 2.0,
+                    background: [background_color.x, background_color.y, background_color.z, 1.0],
+                    grid_opacity: if draw_grid {Some(grid_opacity)} else {None}
+                });
+
+                // older captures blend from the trace color into the background, so they never look muddy on top of it
+                if draw_afterglow {
+                    let strength = if afterglow_grid_opacity {grid_opacity as f32 / 255.0} else {1.0};
+                    for (age, samples) in afterglow.iter().enumerate().rev() {
+                        let weight = afterglow_weight(age, afterglow_traces, afterglow_curve == 1) * strength;
+                        let blend = |trace: f32, background: f32| background + (trace - background) * weight;
+                        let glow_color = color::ImColor32::from_rgba_f32s(blend(trace_color.x, background_color.x), blend(trace_color.y, background_color.y), blend(trace_color.z, background_color.z), 1.0);
+                        draw_raw_trace(samples, interp_data.len(), &draw_list, index_start, &TraceStyle {color: glow_color, ..trace_style}, &ui);
+                    }
+                }
+
+                // how often the recent captures went through each spot, underneath all traces
+                if draw_heatmap {
+                    let (columns, rows) = ((window_width / HEATMAP_CELL).ceil() as usize, (window_height / HEATMAP_CELL).ceil() as usize);
+                    heatmap.set_view(columns, rows, vec![index_start as f32 + view_fraction, x_scale, y_offset, trace_pixels_per_unit]);
+                    if heatmap_new_trace {
+                        heatmap_new_trace = false;
+                        let points: Vec<[f32; 2]> = waveform_buffer.iter().enumerate().map(|(i, sample)| {
+                            let x = raw_index_to_x(i, waveform_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset);
+                            let y = sample_to_y(*sample, trace_pixels_per_unit, y_offset, &ui);
+                            [(x - win_x) / HEATMAP_CELL, (y - win_y) / HEATMAP_CELL]
+                        }).collect();
+                        heatmap.add_trace(&points, heatmap_traces);
+                    }
+                    let most_hits = heatmap.hits.iter().cloned().max().unwrap_or(0).max(1);
+                    for (cell, hits) in heatmap.hits.iter().enumerate().filter(|(_, hits)| **hits > 0) {
+                        let x = win_x + (cell % heatmap.width) as f32 * HEATMAP_CELL;
+                        let y = win_y + (cell / heatmap.width) as f32 * HEATMAP_CELL;
+                        draw_list.add_rect([x, y], [x + HEATMAP_CELL, y + HEATMAP_CELL], heat_color(*hits as f32 / most_hits as f32)).filled(true).build();
+                    }
                 }
 
+                // dense traces are a lot cheaper to draw without anti-aliasing
+                let draw_list_flags = set_antialiasing(trace_antialiasing);
+
+                // a held trace from before a failed capture is drawn dimmed
+                let trace_color = if trace_stale {Vector4::from([trace_color.x, trace_color.y, trace_color.z, trace_color.w * 0.4])} else {trace_color};
+
                 // draw main trace
                 if draw_main_trace {
-                    if !draw_dots {
+                    // the fill goes underneath the lines or dots
+                    if draw_fill {
+                        draw_trace_fill(&interp_data, &draw_list, index_start, index_end, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w * fill_alpha), ..trace_style}, &ui);
+                    }
+                    // the raw samples as steps, nothing interpolated is shown
+                    if draw_hold {
+                        draw_raw_hold(&waveform_buffer, interp_data.len(), &draw_list, index_start, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w), ..trace_style}, &ui);
+                    }
+                    else if !draw_dots {
                         // draw lines at half opacity
-                        draw_trace_lines(&interp_data, &draw_list, color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w / 2.0), index_start, index_end,(x_offset as f32, y_offset), (x_scale / 2.0, y_scale),trace_thickness ,&ui);
+                        let draw_start = Instant::now();
+                        let draw_lines = if batch_lines {draw_trace_lines} else {draw_trace_segments};
+                        draw_lines(&interp_data, &draw_list, index_start, index_end, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w / 2.0), ..trace_style}, &ui);
+                        trace_draw_time = draw_start.elapsed().as_secs_f32() * 1000.0;
                         // draw dots over it
-                        draw_trace_dots(&interp_data, &draw_list, color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w / 2.0), index_start, index_end,(x_offset as f32, y_offset), (x_scale / 2.0, y_scale),trace_thickness ,&ui);
+                        draw_trace_dots(&interp_data, &draw_list, index_start, index_end, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w / 2.0), ..trace_style}, &ui);
                     }
                     else {
-                        draw_trace_dots(&interp_data, &draw_list, color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w), index_start, index_end,(x_offset as f32, y_offset), (x_scale / 2.0, y_scale),trace_thickness ,&ui);
+                        draw_trace_dots(&interp_data, &draw_list, index_start, index_end, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w), ..trace_style}, &ui);
                     }
                     
                 }
                 
+                // draw the reference trace
+                if let Some(reference_data) = &display_frame.reference_data {
+                    let reference_end = index_end.min(reference_data.len() - 1);
+                    let reference_style = TraceStyle {color: color::ImColor32::from_rgba_f32s(reference_color.x, reference_color.y, reference_color.z, reference_color.w), offsets: (x_offset, reference_display.y_offset), scales: (x_scale / 2.0, reference_pixels_per_unit), ..trace_style};
+                    // past the end of the reference's own capture it is only held at its last value, so dim that part
+                    if display_frame.reference_covered < reference_end {
+                        draw_trace_lines(reference_data, &draw_list, index_start, reference_end, &TraceStyle {color: color::ImColor32::from_rgba_f32s(reference_color.x, reference_color.y, reference_color.z, reference_color.w * 0.3), ..reference_style}, &ui);
+                    }
+                    draw_trace_lines(reference_data, &draw_list, index_start, reference_end.min(display_frame.reference_covered), &reference_style, &ui);
+                }
+
+                // show exactly what the scope sent, without any interpolation
+                if draw_stems {
+                    draw_raw_stems(&waveform_buffer, interp_data.len(), &draw_list, index_start, &TraceStyle {color: color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w), ..trace_style}, &ui);
+                }
+
+                // draw the other channels, each with its own placement and volts per division
+                for (trace, data) in extra_traces.iter().zip(display_frame.extra_data.iter()) {
+                    if data.is_empty() {
+                        continue;
+                    }
+                    let display = placed_display(&channel_display, trace.channel, lock_baseline);
+                    let color = channel_colors[trace.channel.index()];
+                    let extra_pixels_per_unit = pixels_per_unit(&trace.voltage_per_div, window_height, display.y_scale);
+                    draw_trace_lines(data, &draw_list, index_start, index_end.min(data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba_f32s(color.x, color.y, color.z, color.w), offsets: (x_offset, display.y_offset), scales: (x_scale / 2.0, extra_pixels_per_unit), ..trace_style}, &ui);
+                }
+
+                // draw what changed since the previous capture
+                if let Some(difference_data) = &display_frame.difference_data {
+                    draw_trace_lines(difference_data, &draw_list, index_start, index_end.min(difference_data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba_f32s(difference_color.x, difference_color.y, difference_color.z, difference_color.w), ..trace_style}, &ui);
+                }
+
+                // draw the comparison method over the main trace
+                if let Some(compare_data) = &display_frame.compare_data {
+                    draw_trace_lines(compare_data, &draw_list, index_start, index_end.min(compare_data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba_f32s(compare_color.x, compare_color.y, compare_color.z, compare_color.w), ..trace_style}, &ui);
+                }
+
+                // the ideal shape, sampled like the capture and stretched over the drawn trace
+                if show_ideal && waveform_buffer.len() > 1 && interp_data.len() > 1 {
+                    // the fit is in volts the right way up, the samples are stored upside down
+                    let ideal: Vec<f64> = processing::ideal_waveform(ideal_shape, &ideal_fit, processing::sample_rate(&time_per_div), waveform_buffer.len()).iter().map(|sample| -sample).collect();
+                    let ideal_data = processing::interpolate_range(&ideal, 0.0, (ideal.len() - 1) as f64, interp_data.len());
+                    draw_trace_lines(&ideal_data, &draw_list, index_start, index_end.min(ideal_data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba(0, 200, 255, 160), ..trace_style}, &ui);
+                }
+
+                // the filtered trace as a ghost over the unfiltered one
+                if let Some(preview_data) = &display_frame.preview_data {
+                    draw_trace_lines(preview_data, &draw_list, index_start, index_end.min(preview_data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba(255, 255, 255, 110), ..trace_style}, &ui);
+                }
+
+                // the error against linear interpolation, around the center line of the trace
+                if let Some(residual_data) = &display_frame.residual_data {
+                    draw_trace_lines(residual_data, &draw_list, index_start, index_end.min(residual_data.len() - 1), &TraceStyle {color: color::ImColor32::from_rgb(230, 80, 230), scales: (x_scale / 2.0, trace_pixels_per_unit * residual_gain), thickness: 1.0, ..trace_style}, &ui);
+                }
+
                 // draw moving average trace
-                if draw_average && avg_window_size < interp_data_lin.len() {
-                    // calculate moving averages from the linearly interpolated trace
-                    // x * time_per_div 
-                    let mut moving_avg = processing::make_rectangle(voltage_per_div.value, voltage_per_div.value, time_per_div.value, 3.0);
-                    
-                    // sample down to main trace size
-                    moving_avg = processing::linear_interpolate_samples(&moving_avg, interpol_samples, time_per_div.value, 1);
-                    draw_trace_lines(&moving_avg, &draw_list, color::ImColor32::from_rgba_f32s(avg_color.x, avg_color.y, avg_color.z,avg_color.w), index_start, index_end, (x_offset as f32, y_offset), (x_scale / 2.0, y_scale),avg_thickness, &ui);
+                if let Some(moving_avg) = display_frame.moving_average.as_ref().filter(|_| avg_window_size < interp_data_lin.len()) {
+                    draw_trace_lines(moving_avg, &draw_list, index_start, index_end.min(moving_avg.len() - 1), &TraceStyle {color: color::ImColor32::from_rgba_f32s(avg_color.x, avg_color.y, avg_color.z,avg_color.w), thickness: avg_thickness, ..trace_style}, &ui);
+                }
+                restore_draw_list_flags(draw_list_flags);
+
+                // show how old the trace on screen is once captures start failing
+                if trace_stale {
+                    if let Some(last) = last_good_capture {
+                        draw_list.add_text([win_x + 8.0, win_y + 8.0], color::ImColor32::from_rgb(255, 200, 60), format!("STALE ({:.1}s)", last.elapsed().as_secs_f32()));
+                    }
+                }
+
+                if no_response {
+                    draw_list.add_text([win_x + 8.0, win_y + 22.0], color::ImColor32::from_rgb(255, 60, 60), "NO RESPONSE FROM SCOPE");
                 }
+
+                if settings_changed {
+                    draw_list.add_text([win_x + 8.0, win_y + 36.0], color::ImColor32::from_rgb(255, 200, 60), "SETTINGS CHANGED - RECAPTURE");
+                }
+
+                // warn when the signal runs into the rails of the ADC
+                let clipped = processing::count_clipped(&raw_buffer);
+                if !raw_buffer.is_empty() && clipped as f32 / raw_buffer.len() as f32 > CLIP_WARNING_FRACTION {
+                    let clip_color = color::ImColor32::from_rgb(255, 40, 40);
+                    draw_list.add_text([win_x + window_width - 70.0, win_y + 8.0], clip_color, "CLIPPING");
+
+                    // mark the clipped samples along the top of the window
+                    if highlight_clipping {
+                        for (i, sample) in raw_buffer.iter().enumerate() {
+                            if *sample == 0 || *sample == 255 {
+                                let x = raw_index_to_x(i, raw_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset);
+                                draw_list.add_rect([x - 1.0, win_y], [x + 1.0, win_y + 6.0], clip_color).filled(true).build();
+                            }
+                        }
+                    }
+                }
+
+                // label the highest and the lowest sample of the capture
+                if mark_peaks {
+                    if let Some((min_index, max_index)) = processing::extremes(&waveform_buffer) {
+                        let peak_color = color::ImColor32::from_rgb(120, 220, 255);
+                        let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                        // stored upside down, so the smallest stored sample is the highest voltage
+                        for (index, label, label_offset) in [(min_index, "Max", -18.0), (max_index, "Min", 6.0)] {
+                            let x = raw_index_to_x(index, waveform_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset);
+                            let y = sample_to_y(waveform_buffer[index], trace_pixels_per_unit, y_offset, &ui);
+                            let time = processing::time_since(index, time_zero, seconds_per_div);
+                            draw_list.add_circle([x, y], 4.0, peak_color).build();
+                            draw_list.add_text([x + 6.0, y + label_offset], peak_color, format!("{label}: {:.3}{} @ {}", -waveform_buffer[index], voltage_per_div.unit_name, time_text(time, seconds_per_div, time_in_divisions)));
+                        }
+                    }
+                }
+
+                // the steepest step of the measured samples, drawn over the captured samples it's between
+                if mark_slew {
+                    let between_cursors = draw_cursors && measure_between_cursors;
+                    let start = measurement_start(waveform_buffer.len(), between_cursors, cursor_a, cursor_b);
+                    let measured = measurement_slice(&waveform_buffer, between_cursors, cursor_a, cursor_b);
+                    if let Some(step) = processing::steepest_step(&slew_samples(measured, slew_smoothing)) {
+                        let slew_color = color::ImColor32::from_rgb(255, 170, 60);
+                        let [from, to] = [start + step, start + step + 1].map(|index| [
+                            raw_index_to_x(index, waveform_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset),
+                            sample_to_y(waveform_buffer[index], trace_pixels_per_unit, y_offset, &ui)
+                        ]);
+                        draw_list.add_line(from, to, slew_color).thickness(3.0).build();
+                        draw_list.add_circle([(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0], 5.0, slew_color).build();
+                    }
+                }
+
+                // small triangle along the top edge marks the trigger point
+                if let Some(x) = trigger_x {
+                    let marker_color = color::ImColor32::from_rgb(255, 140, 0);
+                    draw_list.add_triangle([x - 6.0, win_y], [x + 6.0, win_y], [x, win_y + 10.0], marker_color).filled(true).build();
+                }
+
+                // draw measurement cursors
+                if draw_cursors {
+                    let cursor_color = color::ImColor32::from_rgb(255, 220, 0);
+                    for cursor in [cursor_a, cursor_b] {
+                        let x = raw_index_to_x(cursor, waveform_buffer.len(), interp_data.len(), index_start, x_scale / 2.0, win_x + x_offset);
+                        draw_list.add_line([x, win_y], [x, win_y + window_height], cursor_color).build();
+                    }
+                }
+
+                // draw the manual reference levels, the samples are stored upside down
+                if let Some(levels) = reference_levels {
+                    let level_color = color::ImColor32::from_rgba(255, 120, 200, 160);
+                    for level in [levels.low, levels.high] {
+                        let y = sample_to_y(-level, trace_pixels_per_unit, y_offset, &ui);
+                        draw_list.add_line([win_x, y], [win_x + window_width, y], level_color).build();
+                    }
+                }
+
                 // draw things
                 ui.text(format!("{}..{}", index_start, index_end));
                 ui.text(format!("{}{}/div", voltage_per_div.value, voltage_per_div.unit_name));
                 ui.text(format!("{}{}/div", time_per_div.value, time_per_div.unit_name));
                 
                 // only do this if the window is hovered, focused and the mouse position is valid (i.e the window is actively being used)
-                if ui.is_window_hovered() && ui.is_current_mouse_pos_valid() && ui.is_window_focused() {
-                    // change waveform scaling factor when ctrl + scroll
-                    if ui.io().key_ctrl && ui.io().mouse_wheel < 0.0 && y_scale > 0.1 {y_scale += -0.1;}
-                    else if ui.io().key_ctrl && ui.io().mouse_wheel > 0.0 && y_scale < 5.0 {y_scale += 0.1;}
+                // remember which raw sample is under the mouse for the magnifier
+                hovered_raw_index = None;
+                // last raw sample that's on screen, the DC readout follows panning and zooming with it
+                let right_edge = (window_width / (x_scale / 2.0) + index_start as f32 + view_fraction) as f64;
+                displayed_raw_end = ((right_edge * (waveform_buffer.len() as f64 / interp_data.len() as f64)) as usize).min(waveform_buffer.len());
+                if ui.is_window_hovered() && ui.is_current_mouse_pos_valid() {
+                    let display_index = (mouse_x - win_x) / (x_scale / 2.0) + index_start as f32 + view_fraction;
+                    hovered_raw_index = Some(display_index as f64 * (waveform_buffer.len() as f64 / interp_data.len() as f64));
+                }
 
-                    x_offset = x_offset + ui.io().mouse_wheel as usize;
-                    
+                // the captured sample under the mouse, of whichever trace is closest to it vertically
+                if let Some(position) = hovered_raw_index.filter(|_| hover_tooltip) {
+                    // (trace, sample index, y on screen, value the right way up, unit)
+                    let mut candidates: Vec<(String, usize, f32, f64, &str)> = Vec::new();
+                    if let Some(i) = nearest_sample(position, waveform_buffer.len()) {
+                        candidates.push((channel.to_string(), i, sample_to_y(waveform_buffer[i], trace_pixels_per_unit, y_offset, &ui), -waveform_buffer[i], voltage_per_div.unit_name.as_str()));
+                    }
+                    for trace in extra_traces.iter() {
+                        // the other channels are stretched over the same width as the main trace
+                        let trace_position = position * trace.waveform_data.len() as f64 / waveform_buffer.len().max(1) as f64;
+                        if let Some(i) = nearest_sample(trace_position, trace.waveform_data.len()) {
+                            let display = placed_display(&channel_display, trace.channel, lock_baseline);
+                            let y = sample_to_y(trace.waveform_data[i], pixels_per_unit(&trace.voltage_per_div, window_height, display.y_scale), display.y_offset, &ui);
+                            candidates.push((trace.channel.to_string(), i, y, -trace.waveform_data[i], trace.voltage_per_div.unit_name.as_str()));
+                        }
+                    }
+                    let traces = candidates.len();
+                    if let Some((name, index, _, voltage, unit)) = candidates.into_iter().min_by(|a, b| (a.2 - mouse_y).abs().total_cmp(&(b.2 - mouse_y).abs())) {
+                        let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                        let main_index = nearest_sample(position, waveform_buffer.len()).unwrap_or(0);
+                        let time = processing::time_since(main_index, time_zero, seconds_per_div);
+                        ui.tooltip(|| {
+                            if traces > 1 {
+                                ui.text(name);
+                            }
+                            ui.text(format!("Sample {index}"));
+                            ui.text(format!("Time: {}", time_text(time, seconds_per_div, time_in_divisions)));
+                            ui.text(format!("Voltage: {voltage:.3}{unit}"));
+                        });
+                    }
+                }
+
+                if ui.is_window_hovered() && ui.is_current_mouse_pos_valid() && ui.is_window_focused() {
                     // draw red dot cursor
                     let y_coord: f32;
 
                     // set circle y coordinate to trace when snep_to_trace = true
-                    // TODO: make snap to trace less janky
-                    if snap_to_trace {
-                        let mut index = (((mouse_x - win_x).ceil() / (x_scale / 2.0)) + 1.0) as usize + index_start;
-                        if index >= index_end {
-                            index = index_end - 1;
-                        }
-                        ui.text(format!("{}", index));
-                        y_coord = (((interp_data[index] as f32 * 255.0 * y_scale) + (win_y + 5.0) + (ui.window_size()[1]/2.0))) + y_offset;
+                    if snap_mode == SnapMode::Trace && !interp_data.is_empty() && !waveform_buffer.is_empty() {
+                        // work out the position as a float first, a mouse left of the window or a tiny x scale
+                        // would otherwise wrap around or overflow when turned into an index
+                        let last_index = index_end.min(interp_data.len()).max(1) - 1;
+                        let position = (mouse_x - win_x) / (x_scale / 2.0).max(f32::EPSILON) + view_fraction + index_start as f32;
+                        let index = if position.is_finite() {position.round().clamp(0.0, last_index as f32) as usize} else {last_index};
+                        // map the index of the drawn trace back to the raw samples to get the time
+                        let raw_index = (index as f64 * (waveform_buffer.len() as f64 / interp_data.len() as f64)).min((waveform_buffer.len() - 1) as f64);
+                        let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                        let time = processing::time_since(raw_index.round() as usize, time_zero, seconds_per_div);
+                        ui.text(format!("Sample {}, {}", index, time_text(time, seconds_per_div, time_in_divisions)));
+                        y_coord = sample_to_y(interp_data[index], trace_pixels_per_unit, y_offset, &ui);
                         draw_list.add_circle([mouse_x,y_coord], 2.0, color::ImColor32::from_rgb(255, 255, 255)).filled(true).build();
-                        draw_list.add_text([mouse_x - 4.0, y_coord + 6.0], color::ImColor32::from_rgb(255, 255, 255), format!("Voltage: {:.3}{}", -(interp_data[index]), voltage_per_div.unit_name));
+                        // the dot sits on the drawn trace, but the reading comes from the captured samples
+                        // both are stored upside down, so the reading is negated just like the grid snap and the probe
+                        let voltage = -processing::value_at(&waveform_buffer, raw_index);
+                        draw_list.add_text([mouse_x - 4.0, y_coord + 6.0], color::ImColor32::from_rgb(255, 255, 255), format!("Voltage: {:.3}{}", voltage, voltage_per_div.unit_name));
+                        draw_list.add_text([mouse_x - 4.0, y_coord + 20.0], color::ImColor32::from_rgb(255, 255, 255), format!("Time: {}", time_text(time, seconds_per_div, time_in_divisions)));
+                    }
+                    // snap to the closest grid intersection and read off the time and voltage there
+                    else if snap_mode == SnapMode::Grid {
+                        // the grid is drawn 5 pixels down, like the traces
+                        let (div_width, div_height) = (window_width / HORIZONTAL_DIVISIONS, window_height / VERTICAL_DIVISIONS);
+                        let grid_x = win_x + ((mouse_x - win_x) / div_width).round() * div_width;
+                        let grid_y = win_y + 5.0 + ((mouse_y - win_y - 5.0) / div_height).round() * div_height;
+
+                        let display_index = (grid_x - win_x) / (x_scale / 2.0) + index_start as f32 + view_fraction;
+                        let raw_index = display_index as f64 * (waveform_buffer.len() as f64 / interp_data.len() as f64);
+                        let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                        let time = processing::time_since(raw_index.round().max(0.0) as usize, time_zero, seconds_per_div);
+                        // invert sample_to_y, the samples are stored upside down
+                        let voltage = -((grid_y - sample_to_y(0.0, trace_pixels_per_unit, y_offset, &ui)) / trace_pixels_per_unit);
+
+                        draw_list.add_circle([grid_x, grid_y], 3.0, color::ImColor32::from_rgb(255, 255, 255)).build();
+                        draw_list.add_text([grid_x + 4.0, grid_y + 6.0], color::ImColor32::from_rgb(255, 255, 255), format!("Voltage: {:.3}{}", voltage, voltage_per_div.unit_name));
+                        draw_list.add_text([grid_x + 4.0, grid_y + 20.0], color::ImColor32::from_rgb(255, 255, 255), format!("Time: {}", time_text(time, seconds_per_div, time_in_divisions)));
                     }
                 }
             }
@@ -530,52 +2682,261 @@ fn main() {
             let mut current_config = (**mutex).clone();
 
             ui.window("Capture Controls")
-                .size([300.0,200.0], Condition::Appearing)
-                .position([500.0,200.0], Condition::Appearing)
+                .flags(layout_flags)
+                .size([column_width, display_size[1] / 2.0], layout_condition)
+                .position([controls_x, display_size[1] / 2.0], layout_condition)
                 .collapsible(false)
-                .movable(false)
-                .resizable(false)
-                .no_decoration()
                 .build(|| {
                     let _tab = ui.tab_bar("capture_tabs");
                     if let Some(te) = ui.tab_item("Action") {
-                        let disabled = ui.begin_disabled(current_config.open_port | do_capture);   
+                        if current_config.port_name.is_empty() {
+                            ui.text_colored([1.0, 0.8, 0.2, 1.0], "No port selected, see Connection Settings");
+                        }
+                        // can be changed while capturing, like on a scope
+                        ui.text("Trigger Mode");
+                        ui.same_line();
+                        ui.radio_button("Auto##trigger_mode", &mut trigger_mode, TriggerMode::Auto);
+                        ui.same_line();
+                        ui.radio_button("Normal##trigger_mode", &mut trigger_mode, TriggerMode::Normal);
+                        ui.same_line();
+                        ui.radio_button("Single##trigger_mode", &mut trigger_mode, TriggerMode::Single);
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text("Keeps capturing in the background until the trigger fires, then stops");
+                        }
+
+                        let disabled = ui.begin_disabled(current_config.open_port | do_capture | current_config.port_name.is_empty());   
                         ui.radio_button("Test Connection", &mut mode_radiobutton, 0);
                         ui.radio_button("Get Conditions", &mut mode_radiobutton, 1);
                         ui.radio_button("Get Waveform", &mut mode_radiobutton, 2);
+                        ui.radio_button("Run Diagnostics", &mut mode_radiobutton, 3);
 
-                        if ui.button_with_size("Capture Single", [150.0,25.0]) && !do_capture {
+                        if ui.button_with_size("Run", [150.0,25.0]) && !do_capture {
                             do_capture = true;
-                            single_capture = true;
+                            capture_once = false;
+                            periodic_capture = false;
+                            last_capture_time = None;
+                            triggered = false;
                         };
+                        ui.same_line();
+                        if ui.button_with_size("Capture Once", [150.0,25.0]) && !do_capture {
+                            do_capture = true;
+                            capture_once = true;
+                            periodic_capture = false;
+                        };
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text("Takes one capture without waiting for the trigger");
+                        }
+                        // slow logging, one capture every few seconds
+                        if ui.button_with_size("Capture Periodic", [150.0,25.0]) && !do_capture {
+                            do_capture = true;
+                            capture_once = false;
+                            periodic_capture = true;
+                            last_capture_time = None;
+                            triggered = false;
+                        };
+                        ui.slider("Period (s)", 1.0, 600.0, &mut capture_period_secs);
+
+                        // push the current trace back into one of the scope's save channels
+                        ui.disabled(voltage_per_div.value == 0.0, || {
+                            for (label, save_channel) in [("Store to Save 1", Channel::SAVE1), ("Store to Save 2", Channel::SAVE2)] {
+                                if ui.button_with_size(label, [150.0,25.0]) && !do_capture {
+                                    let data = processing::unscale_waveform_data(&waveform_buffer, &voltage_per_div);
+                                    // send_wi would refuse it anyway, this way it's clear why
+                                    if data.contains(&0x0D) {
+                                        store_message = Some(String::from("Not stored, a sample comes out as the byte 13 (CR) that ends the Wi frame"));
+                                        continue;
+                                    }
+                                    store_message = None;
+                                    current_config.wi_data = data;
+                                    store_channel = Some(save_channel);
+                                    do_capture = true;
+                                }
+                            }
+                        });
+                        if let Some(message) = &store_message {
+                            ui.text_colored([1.0, 0.4, 0.4, 1.0], message);
+                        }
+                        // check the front panel settings without waiting for a whole capture
+                        if ui.button_with_size("Read Settings", [150.0,25.0]) && !do_capture {
+                            read_settings = true;
+                            do_capture = true;
+                        }
                         disabled.end();
 
                         ui.disabled(!do_capture, || {
                             do_capture = !ui.button_with_size("Stop", [150.0,25.0]) && do_capture;
                         });
+                        ui.checkbox("Clear on Failure", &mut clear_on_failure);
+                        ui.same_line();
+                        ui.checkbox("Rescale on Change", &mut rescale_on_change);
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text("When reading the settings finds the scope at another scale, redraw the trace at it instead of clearing it");
+                        }
+                        if do_capture && trigger_mode != TriggerMode::Auto && !capture_once && !triggered {
+                            ui.same_line();
+                            ui.text("Armed");
+                        }
+                        else if triggered {
+                            ui.same_line();
+                            ui.text("Triggered");
+                        }
                         // set all to false
                         current_config.do_ri = false;
                         current_config.do_ro = false;
                         current_config.do_s1 = false;
+                        current_config.do_wi = false;
+                        current_config.do_diag = false;
                         
                         match mode_radiobutton {
                             0 => {current_config.do_s1 = true;},
                             1 => {current_config.do_ro = true;},
                             2 => {current_config.do_ri = true;},
+                            3 => {current_config.do_diag = true;},
                             _ => {}
                         }
+
+                        // a pending store overrides the selected mode
+                        if let Some(save_channel) = store_channel {
+                            current_config.do_s1 = false;
+                            current_config.do_ro = false;
+                            current_config.do_ri = false;
+                            current_config.do_diag = false;
+                            current_config.do_wi = true;
+                            current_config.wi_channel = save_channel;
+                        }
+                        else if read_settings {
+                            current_config.do_s1 = false;
+                            current_config.do_ri = false;
+                            current_config.do_diag = false;
+                            current_config.do_ro = true;
+                        }
                         
                         current_config.do_capture = do_capture;
                         current_config.open_port = do_capture;
+                        current_config.capture_period = if periodic_capture && do_capture {Some(Duration::from_secs_f32(capture_period_secs))} else {None};
+                        if periodic_capture && do_capture {
+                            // counted from the last capture that came in
+                            match last_capture_time {
+                                Some(last) => ui.text(format!("Next capture in {:.0}s", (capture_period_secs - last.elapsed().as_secs_f32()).max(0.0))),
+                                None => ui.text("Waiting for the first capture")
+                            }
+                        }
+
+                        // show how fast the link can possibly go compared to what we actually get
+                        let min_interval = min_capture_interval(&current_config);
+                        ui.text(format!("Min. interval: {:.1}s @ {} Baud", min_interval.as_secs_f32(), current_config.baudrate));
+                        if let Some(interval) = capture_interval {
+                            ui.same_line();
+                            ui.text(format!("Measured: {:.1}s", interval.as_secs_f32()));
+                        }
+                        if capture_interval.is_some_and(|interval| interval > min_interval * 2) {
+                            ui.text_colored([1.0, 0.8, 0.2, 1.0], "Warning: Captures are retrying, link can't keep up");
+                        }
                         te.end();
                     };
 
+                    if let Some(te) = ui.tab_item("Trigger") {
+                        ui.input_scalar("Level", &mut trigger.level).build();
+                        ui.radio_button("Rising", &mut trigger.slope, TriggerSlope::Rising);
+                        ui.same_line();
+                        ui.radio_button("Falling", &mut trigger.slope, TriggerSlope::Falling);
+                        ui.text("Coupling");
+                        ui.same_line();
+                        ui.radio_button("DC", &mut trigger.coupling, TriggerCoupling::Dc);
+                        ui.same_line();
+                        ui.radio_button("AC", &mut trigger.coupling, TriggerCoupling::Ac);
+                        ui.same_line();
+                        ui.radio_button("Noise Reject", &mut trigger.coupling, TriggerCoupling::NoiseReject);
+                        // ignore edges for this many samples after a trigger, so bursts only trigger on their first edge
+                        ui.input_scalar("Holdoff (samples)", &mut trigger.holdoff_samples).step(10).build();
+                        ui.separator();
+                        // for signals without a clean edge, line captures up with the first one instead
+                        if ui.checkbox("Stabilize", &mut stabilize) && !stabilize {
+                            stabilize_reference.clear();
+                        }
+                        ui.separator();
+                        if ui.checkbox("Show Trigger Point", &mut show_trigger_point) && show_trigger_point {
+                            trigger_sample = trigger_index(&waveform_buffer, &trigger);
+                            align_trigger = true;
+                        }
+                        ui.disabled(!show_trigger_point, || {
+                            ui.checkbox("Shade Pre-Trigger", &mut shade_pre_trigger);
+                            let mut position_percent = trigger_position * 100.0;
+                            if ui.slider("Position (%)", 0.0, 100.0, &mut position_percent) {
+                                trigger_position = position_percent / 100.0;
+                                align_trigger = true;
+                            }
+                        });
+                        te.end();
+                    }
+
+                    if let Some(te) = ui.tab_item("Averaging") {
+                        ui.checkbox("Average Captures", &mut average_captures);
+                        ui.slider("Captures", 2, 64, &mut average_count);
+                        if ui.button("Reset") {
+                            average_buffer.clear();
+                            averaged_captures = 0;
+                        }
+                        ui.same_line();
+                        ui.text(format!("{} captures averaged", averaged_captures.min(average_count)));
+                        te.end();
+                    }
+
+                    if let Some(te) = ui.tab_item("Info") {
+                        // the effective connection parameters, handy for documenting captures
+                        let stop_bits = if current_config.two_stopbits {2} else {1};
+                        let parity = match current_config.parity {
+                            Parity::None => "N",
+                            Parity::Odd => "O",
+                            Parity::Even => "E"
+                        };
+                        ui.text(format!("Port: {}", current_config.port_name));
+                        ui.text(format!("Settings: {} Baud, {}{}{}", current_config.baudrate, data_bits_count(current_config.data_bits), parity, stop_bits));
+                        ui.text(format!("Channel: {}", current_config.channel));
+                        ui.text(format!("Dropped frames: {}", shared.lock().map(|state| state.dropped_frames).unwrap_or(0)));
+                        ui.separator();
+                        // what actually arrived compared to what is drawn, a short read shows up here first
+                        if last_good_capture.is_some() {
+                            let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                            let text = format!("Samples: {} of {}, {} drawn", waveform_buffer.len(), current_config.record_length, display_frame.interp_data.len());
+                            if waveform_buffer.len() < current_config.record_length as usize {
+                                ui.text_colored([1.0, 0.8, 0.2, 1.0], text);
+                            } else {
+                                ui.text(text);
+                            }
+                            ui.text(format!("Resolution: {}/sample", processing::format_si(processing::scale_time(1, seconds_per_div, 1.0), "s")));
+                        }
+                        else {
+                            ui.text("Samples: no capture yet");
+                        }
+                        if let Some(conditions) = &capture_conditions {
+                            ui.text(format!("{}{}/div, {}{}/div", conditions.voltage_per_div.value, conditions.voltage_per_div.unit_name, conditions.time_per_div.value, conditions.time_per_div.unit_name));
+                            ui.text_wrapped(conditions.fields.join(", "));
+                        }
+                        else {
+                            ui.text("No capture conditions yet");
+                        }
+                        te.end();
+                    }
+
                     if let Some(te) = ui.tab_item("Channel") {
                         let disabled = ui.begin_disabled(current_config.open_port); 
+                        let previous_channel = channel;
                         ui.radio_button("Display 1", &mut channel, Channel::DISPLAY1);
                         ui.radio_button("Display 2", &mut channel, Channel::DISPLAY2);
                         ui.radio_button("Save 1", &mut channel, Channel::SAVE1);
                         ui.radio_button("Save 2", &mut channel, Channel::SAVE2);
+                        // another channel's signal can't be rescaled into this one's, so the trace goes either way
+                        if channel != previous_channel && last_good_capture.is_some() && replay.is_empty() && scrollback_view.is_none() {
+                            waveform_buffer = vec![0.0f64; waveform_buffer.len().max(MIN_WAVEFORM_SAMPLES)];
+                            raw_buffer.clear();
+                            settings_changed = true;
+                            // nothing of the old channel belongs with the new one
+                            average_buffer.clear();
+                            averaged_captures = 0;
+                            afterglow.clear();
+                            stabilize_reference.clear();
+                        }
 
                         ui.next_column();
                         ui.text("Capture channel");
@@ -583,6 +2944,64 @@ fn main() {
                         current_config.channel = channel;
                         disabled.end();
 
+                        // where this channel's traces are drawn, doesn't change what is captured
+                        ui.separator();
+                        let display = &mut channel_display[channel.index()];
+                        // with the baseline locked the center grid line is always 0V
+                        ui.checkbox("Lock Baseline to Center", &mut lock_baseline);
+                        ui.same_line();
+                        if ui.button("Zero Y Offset") {
+                            display.y_offset = 0.0;
+                        }
+                        ui.disabled(lock_baseline, || {
+                            ui.slider("Y Offset", -200.0, 200.0, &mut display.y_offset);
+                        });
+                        precise_slider(&ui, "Y Scale", SCALE_RANGE.0, SCALE_RANGE.1, &mut display.y_scale);
+
+                        // corrects the captured values, unlike the placement above
+                        ui.separator();
+                        let calibration = &mut current_config.calibration[channel.index()];
+                        ui.input_scalar("Gain", &mut calibration.gain).step(0.001).display_format("%.4f").build();
+                        ui.input_scalar(format!("Offset ({})", voltage_per_div.unit_name), &mut calibration.offset).step(0.1).display_format("%.3f").build();
+                        // whatever a grounded probe still reads is the offset, on top of the current calibration
+                        if ui.button("Measure Offset") && !waveform_buffer.is_empty() {
+                            calibration.offset += processing::mean(&waveform_buffer);
+                        }
+                        if ui.is_item_hovered() {
+                            ui.tooltip_text("Ground the probe and capture this channel first");
+                        }
+                        ui.same_line();
+                        if ui.button("Reset Calibration") {
+                            *calibration = Calibration::default();
+                        }
+                        // interpolates over points a dropped or corrupted byte threw off, the rest stays exactly as captured
+                        let mut despike = current_config.despike_sigma.is_some();
+                        let mut despike_sigma = current_config.despike_sigma.unwrap_or(DEFAULT_DESPIKE_SIGMA);
+                        ui.checkbox("Remove Spikes", &mut despike);
+                        ui.same_line();
+                        ui.disabled(!despike, || {
+                            ui.set_next_item_width(100.0);
+                            ui.slider("Threshold (sigma)", 2.0, 20.0, &mut despike_sigma);
+                        });
+                        current_config.despike_sigma = if despike {Some(despike_sigma)} else {None};
+
+                        // grab other channels in the same acquisition and draw them on top
+                        ui.separator();
+                        ui.text("Acquire");
+                        for acquired in CHANNELS {
+                            let _id = ui.push_id_usize(acquired.index());
+                            // the selected channel is the main trace, it is always acquired
+                            let main = acquired == current_config.channel;
+                            let mut enabled = main || current_config.acquire_channels[acquired.index()];
+                            ui.disabled(current_config.open_port || main, || {
+                                if ui.checkbox(format!("{acquired}"), &mut enabled) {
+                                    current_config.acquire_channels[acquired.index()] = enabled;
+                                }
+                            });
+                            ui.same_line();
+                            ui.color_edit4_config("Overlay", &mut channel_colors[acquired.index()]).inputs(false).build();
+                        }
+
                         //let mut current_config = (**mutex).clone();
 
                         te.end();
@@ -613,7 +3032,7 @@ fn main() {
                     };
 
                 if CollapsingHeader::new("Port Name")
-                    .default_open(false)
+                    .default_open(availible_ports.is_empty())
                     .build(&ui) {
                         let disabled = ui.begin_disabled(current_config.open_port);
                         if let Some(_) = ui.begin_combo("Port", &port_string) {
@@ -627,10 +3046,94 @@ fn main() {
                                 if selected {port_string = port.clone();current_config.port_name = port.clone();} 
                             }
                         };
+
+                        if availible_ports.is_empty() {
+                            ui.text_wrapped("No ports found - plug in your adapter and click Refresh");
+                        }
+                        if ui.button("Refresh") {
+                            let (ports, preferred) = scan_ports();
+                            availible_ports = ports;
+                            // keep the selected port if it's still there
+                            if !availible_ports.contains(&port_string) {
+                                port_string = preferred.unwrap_or_default();
+                                current_config.port_name = port_string.clone();
+                            }
+                        }
                         disabled.end();
                 }
                 
-                ui.disabled(current_config.open_port, || {ui.checkbox("2 Stop Bits", &mut current_config.two_stopbits);});
+                ui.disabled(current_config.open_port, || {
+                    ui.checkbox("2 Stop Bits", &mut current_config.two_stopbits);
+                    // 8N1 works for the OS3000, some clones and adapters need something else
+                    let mut data_bits_index = DATA_BITS.iter().position(|bits| *bits == current_config.data_bits).unwrap_or(3);
+                    if ui.combo("Data Bits", &mut data_bits_index, &DATA_BITS, |bits| data_bits_count(*bits).to_string().into()) {
+                        current_config.data_bits = DATA_BITS[data_bits_index];
+                    }
+                    let mut parity_index = PARITIES.iter().position(|parity| *parity == current_config.parity).unwrap_or(0);
+                    if ui.combo("Parity", &mut parity_index, &PARITIES, |parity| parity.to_string().into()) {
+                        current_config.parity = PARITIES[parity_index];
+                    }
+                });
+                // the framing is only read when the port is opened, so don't pretend it can change mid-capture
+                if current_config.open_port {
+                    ui.text_disabled("Stop capturing to change the framing");
+                }
+
+                if CollapsingHeader::new("Advanced")
+                    .default_open(false)
+                    .build(&ui) {
+                        // scopes other than the OS3000 can have a different number of points per record
+                        ui.disabled(current_config.open_port, || {
+                            if ui.combo_simple_string("Preset", &mut acquisition_preset, &ACQUISITION_PRESETS) {
+                                apply_acquisition_preset(&mut current_config, acquisition_preset);
+                            }
+                            // touching any of the timing settings turns the preset into a custom one
+                            let mut changed = false;
+                            changed |= ui.slider("Max Retries (0 = forever)", 0, 50, &mut current_config.max_retries);
+                            changed |= ui.slider("S1 Delay (ms)", 0, 3000, &mut current_config.delays.after_s1_ms);
+                            changed |= ui.slider("Ro Read Delay (ms)", 0, 3000, &mut current_config.delays.before_ro_read_ms);
+                            changed |= ui.slider("Ro Delay (ms)", 0, 3000, &mut current_config.delays.after_ro_ms);
+                            changed |= ui.slider("Ri Read Delay (ms)", 0, 3000, &mut current_config.delays.before_ri_read_ms);
+                            changed |= ui.slider("Record Length", 100, 4000, &mut current_config.record_length);
+                            // more points than one record, read in blocks of the record length
+                            let mut deep = current_config.deep_record.is_some();
+                            let mut deep_points = current_config.deep_record.unwrap_or(os3000::DEFAULT_RECORD_LENGTH * 4);
+                            ui.checkbox("Deep Record", &mut deep);
+                            ui.same_line();
+                            ui.disabled(!deep, || {
+                                ui.slider("Points", current_config.record_length, os3000::MAX_RI_ADDRESS, &mut deep_points);
+                            });
+                            current_config.deep_record = if deep {Some(deep_points)} else {None};
+                            // how the Ri sample bytes are interpreted, the OS3000 uses offset binary bytes
+                            // Auto only tells the two 8 bit formats apart and gets large square waves wrong, so it's not the default
+                            ui.text("Sample Format");
+                            ui.radio_button("Auto", &mut current_config.sample_format, SampleFormat::Auto);
+                            ui.same_line();
+                            ui.radio_button("U8 (offset 128)", &mut current_config.sample_format, SampleFormat::U8Offset128);
+                            ui.same_line();
+                            ui.radio_button("I8", &mut current_config.sample_format, SampleFormat::I8);
+                            ui.radio_button("U16 LE", &mut current_config.sample_format, SampleFormat::U16LE);
+                            ui.same_line();
+                            ui.radio_button("U16 BE", &mut current_config.sample_format, SampleFormat::U16BE);
+                            // a slow scope sends the Ri frame in several chunks
+                            changed |= ui.slider("Ri Read Attempts", 1, 32, &mut current_config.ri_read_attempts);
+                            // firmware revisions differ in how long the header in front of the Ri samples is
+                            let mut override_header = current_config.ri_header_length.is_some();
+                            if ui.checkbox("Override Ri Header Length", &mut override_header) {
+                                current_config.ri_header_length = if override_header {Some(os3000::RI_HEADER_LENGTH)} else {None};
+                            }
+                            if let Some(header_length) = current_config.ri_header_length.as_mut() {
+                                ui.slider("Ri Header Length", 0, 32, header_length);
+                            }
+                            else if ui.is_item_hovered() {
+                                ui.tooltip_text("Detected from the frames, checked against the command echo");
+                            }
+                            if changed {
+                                acquisition_preset = CUSTOM_PRESET;
+                            }
+                        });
+                        ui.slider("Queue Depth", 1, MAX_QUEUE_DEPTH, &mut current_config.queue_depth);
+                }
                 a.end();
             }
             });
@@ -639,20 +3142,825 @@ fn main() {
         };
         //ui.show_demo_window(&mut show_demo);
 
+        // the eye diagram and magnifier show the live trace, so they use its channel's placement
+        let live_display = placed_display(&channel_display, channel, lock_baseline);
+
+        if show_eye {
+            ui.window("Eye Diagram")
+                .size([400.0,300.0], Condition::Appearing)
+                .position([100.0,50.0], Condition::Appearing)
+                .opened(&mut show_eye)
+                .build(|| {
+                    ui.checkbox("Auto Period", &mut eye_auto_period);
+                    ui.disabled(eye_auto_period, || {
+                        ui.slider("Period (samples)", 2, 500, &mut eye_period);
+                    });
+                    ui.slider("Persistence", 1, 1000, &mut eye_persistence);
+                    if ui.button("Clear") {
+                        eye_slices.clear();
+                    }
+                    ui.same_line();
+                    ui.text(format!("{} slices", eye_slices.len()));
+
+                    ui.child_window("eye_plot").build(|| {
+                        let draw_list = ui.get_window_draw_list();
+                        let (window_width, window_height) = ui.window_size().into();
+                        let (win_x, win_y) = ui.window_pos().into();
+                        draw_list.add_rect([win_x, win_y], [win_x + window_width, win_y + window_height], color::ImColor32::from_rgba_f32s(background_color.x, background_color.y, background_color.z, 1.0)).filled(true).build();
+
+                        // every slice is drawn faintly, so the frequently hit paths stand out
+                        let slice_color = color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, 0.15);
+                        for slice in &eye_slices {
+                            let x_step = window_width / (slice.len() - 1) as f32;
+                            draw_trace_lines(slice, &draw_list, 0, slice.len(), &TraceStyle {color: slice_color, offsets: (0.0, live_display.y_offset), scales: (x_step, pixels_per_unit(&voltage_per_div, window_height, live_display.y_scale)), thickness: 1.0}, &ui);
+                        }
+                    });
+                });
+        }
+
+        if show_magnifier {
+            ui.window("Magnifier")
+                .size([300.0,200.0], Condition::Appearing)
+                .position([200.0,150.0], Condition::Appearing)
+                .opened(&mut show_magnifier)
+                .build(|| {
+                    ui.slider("Zoom Span", 2, 200, &mut magnifier_span);
+                    ui.child_window("magnifier_plot").build(|| {
+                        let draw_list = ui.get_window_draw_list();
+                        let (window_width, window_height) = ui.window_size().into();
+                        let (win_x, win_y) = ui.window_pos().into();
+                        draw_list.add_rect([win_x, win_y], [win_x + window_width, win_y + window_height], color::ImColor32::from_rgba_f32s(background_color.x, background_color.y, background_color.z, 1.0)).filled(true).build();
+
+                        // show the samples around the mouse position in the drawing window
+                        if let Some(center) = hovered_raw_index {
+                            let num_points = (window_width as usize).max(2);
+                            let zoomed = processing::interpolate_range(&waveform_buffer, center - magnifier_span as f64, center + magnifier_span as f64, num_points);
+                            let trace = color::ImColor32::from_rgba_f32s(trace_color.x, trace_color.y, trace_color.z, trace_color.w);
+                            draw_trace_lines(&zoomed, &draw_list, 0, zoomed.len(), &TraceStyle {color: trace, offsets: (0.0, live_display.y_offset), scales: (window_width / (num_points - 1) as f32, pixels_per_unit(&voltage_per_div, window_height, live_display.y_scale)), thickness: trace_thickness}, &ui);
+                            // mark the center
+                            draw_list.add_line([win_x + window_width / 2.0, win_y], [win_x + window_width / 2.0, win_y + window_height], color::ImColor32::from_rgba(255, 255, 255, 64)).build();
+                        }
+                    });
+                });
+        }
+
+        if show_measurements {
+            ui.window("Measurements")
+                .size([260.0,180.0], Condition::Appearing)
+                .position([300.0,200.0], Condition::Appearing)
+                .opened(&mut show_measurements)
+                .build(|| {
+                    let last_index = waveform_buffer.len() - 1;
+                    ui.checkbox("Cursors", &mut draw_cursors);
+                    ui.same_line();
+                    ui.disabled(!draw_cursors, || {
+                        ui.checkbox("Between Cursors", &mut measure_between_cursors);
+                    });
+                    ui.disabled(!draw_cursors, || {
+                        ui.slider("Cursor A", 0, last_index, &mut cursor_a);
+                        ui.slider("Cursor B", 0, last_index, &mut cursor_b);
+                    });
+                    // the rise and fall times, duty cycle and mark/space go from these instead of the extremes
+                    let mut manual = reference_levels.is_some();
+                    if ui.checkbox("Manual Levels", &mut manual) && manual {
+                        // start from what the automatic detection would use
+                        let (min, max) = waveform_buffer.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(-*sample), max.max(-*sample)));
+                        if max > min {
+                            manual_levels = ReferenceLevels{low: min, high: max};
+                        }
+                    }
+                    ui.disabled(!manual, || {
+                        ui.set_next_item_width(80.0);
+                        ui.input_scalar(format!("High ({})", voltage_per_div.unit_name), &mut manual_levels.high).display_format("%.3f").build();
+                        ui.same_line();
+                        ui.set_next_item_width(80.0);
+                        ui.input_scalar(format!("Low ({})", voltage_per_div.unit_name), &mut manual_levels.low).display_format("%.3f").build();
+                    });
+                    if manual && manual_levels.high <= manual_levels.low {
+                        ui.text_colored([1.0, 0.8, 0.2, 1.0], "High has to be above Low");
+                    }
+                    reference_levels = if manual {Some(manual_levels)} else {None};
+                    ui.separator();
+
+                    // measure either the whole capture or only the region between the cursors
+                    let samples = measurement_slice(&waveform_buffer, draw_cursors && measure_between_cursors, cursor_a, cursor_b);
+
+                    let measurements = gated_measurements(samples, &raw_buffer, &time_per_div, &voltage_per_div, &Measurement::ALL, reference_levels);
+                    let unit = &measurements.unit;
+                    measurement_text("Vpp", measurements.vpp, unit, &vpp_history, &ui);
+                    measurement_text("Mean", measurements.mean, unit, &mean_history, &ui);
+                    measurement_text("RMS", measurements.vrms, unit, &rms_history, &ui);
+                    // the plain RMS is off by the partial cycles at the ends, this one only takes whole cycles
+                    measurement_text("Cycle RMS", measurements.cycle_rms, unit, &cycle_rms_history, &ui);
+
+                    // power into the load, the RMS value is in the scope's unit so convert it to volts first
+                    let vrms = if voltage_per_div.unit_mult > 0.0 {measurements.vrms.unwrap_or(0.0) / voltage_per_div.unit_mult} else {0.0};
+                    ui.input_scalar("Load (Ohm)", &mut load_impedance).step(1.0).display_format("%.1f").build();
+                    load_impedance = load_impedance.max(0.001);
+                    let dbm = processing::power_dbm(vrms, load_impedance);
+                    if dbm.is_finite() {
+                        ui.text(format!("Power: {} ({:.2}dBm)", processing::format_si(processing::power_watts(vrms, load_impedance), "W"), dbm));
+                    } else {
+                        ui.text("Power: --");
+                    }
+
+                    let sample_rate = processing::sample_rate(&time_per_div);
+                    match measurements.frequency {
+                        Some(frequency) => ui.text(format!("Freq: {}", processing::format_si(frequency, "Hz"))),
+                        None => ui.text("Freq: --")
+                    }
+                    let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                    // spread of the single periods, relative to the average period
+                    match (measurements.period, measurements.period_jitter) {
+                        (Some(period), Some(jitter)) => ui.text(format!("Jitter: {} ({:.2}%)", time_text(jitter, seconds_per_div, time_in_divisions), jitter / period * 100.0)),
+                        _ => ui.text("Jitter: --")
+                    }
+                    let edge_text = |edge: Option<f64>| edge.map(|edge| time_text(edge, seconds_per_div, time_in_divisions)).unwrap_or(String::from("--"));
+                    ui.text(format!("Rise: {}  Fall: {}", edge_text(measurements.rise_time), edge_text(measurements.fall_time)));
+                    // the samples are in the scope's voltage unit, the rate is shown in volts
+                    if sample_rate > 0.0 && voltage_per_div.unit_mult > 0.0 && samples.len() >= 2 {
+                        let slew_rate = processing::max_slew_rate(&slew_samples(samples, slew_smoothing), 1.0 / sample_rate) / voltage_per_div.unit_mult;
+                        ui.text(format!("Slew Rate: {}", slew_rate_text(slew_rate)));
+                    } else {
+                        ui.text("Slew Rate: --");
+                    }
+                    ui.same_line();
+                    ui.checkbox("Smooth##slew", &mut slew_smoothing);
+                    ui.same_line();
+                    ui.checkbox("Mark##slew", &mut mark_slew);
+                    match measurements.duty_cycle {
+                        Some(duty) => ui.text(format!("Duty: {:.1}%", duty * 100.0)),
+                        None => ui.text("Duty: --")
+                    }
+                    match measurements.mark_space {
+                        Some(mark_space) => {
+                            ui.text(format!("Mark: {}  Space: {}", time_text(mark_space.mark, seconds_per_div, time_in_divisions), time_text(mark_space.space, seconds_per_div, time_in_divisions)));
+                            ui.text(format!("Mark:Space {:.3}:1  Asymmetry: {:.1}%", mark_space.ratio, mark_space.asymmetry * 100.0));
+                        },
+                        None => ui.text("Mark: --  Space: --")
+                    }
+                    match (measurements.crest_factor, measurements.form_factor) {
+                        (Some(crest), Some(form)) => ui.text(format!("Crest: {:.3}  Form: {:.3}", crest, form)),
+                        _ => ui.text("Crest: --  Form: --")
+                    }
+
+                    // edges through the middle of the signal, like a counter would see them
+                    let (pulse_min, pulse_max) = samples.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)));
+                    // the samples are stored upside down, so a rising edge falls here
+                    let pulses = processing::count_pulses(samples, (pulse_min + pulse_max) / 2.0, pulse_slope == TriggerSlope::Falling);
+                    let span = processing::scale_time(samples.len().saturating_sub(1), seconds_per_div, 1.0);
+                    if span > 0.0 {
+                        ui.text(format!("Pulses: {} ({})", pulses, processing::format_si(pulses as f64 / span, "/s")));
+                    } else {
+                        ui.text(format!("Pulses: {}", pulses));
+                    }
+                    ui.same_line();
+                    ui.radio_button("Rising##pulses", &mut pulse_slope, TriggerSlope::Rising);
+                    ui.same_line();
+                    ui.radio_button("Falling##pulses", &mut pulse_slope, TriggerSlope::Falling);
+                    // area under the curve from cursor A to cursor B, e.g. the charge of a current pulse
+                    if draw_cursors {
+                        // in volt-seconds, the samples are in the scope's unit and stored upside down
+                        let area = -processing::integral_between(&waveform_buffer, cursor_a, cursor_b, processing::scale_time(1, seconds_per_div, 1.0));
+                        let area = if voltage_per_div.unit_mult > 0.0 {area / voltage_per_div.unit_mult} else {0.0};
+                        ui.text(format!("Area A-B: {}", processing::format_si(area, "Vs")));
+                    }
+                    // how long a step takes to stay within the tolerance of its final value
+                    match processing::settling_time(samples, seconds_per_div, settling_tolerance / 100.0) {
+                        Some(settling) => ui.text(format!("Settling: {}", time_text(settling, seconds_per_div, time_in_divisions))),
+                        None => ui.text("Settling: --")
+                    }
+                    ui.input_scalar("Tolerance (%)", &mut settling_tolerance).step(1.0).display_format("%.1f").build();
+                    settling_tolerance = settling_tolerance.clamp(0.1, 50.0);
+                    // level of a slowly changing signal, leaving out whatever happened before the window
+                    match processing::dc_average(&waveform_buffer, displayed_raw_end, dc_window) {
+                        Some(dc) => ui.text(format!("DC: {:.3}{}", -dc, voltage_per_div.unit_name)),
+                        None => ui.text("DC: --")
+                    }
+                    precise_slider(&ui, "DC Window", 1, waveform_buffer.len().max(1), &mut dc_window);
+                    // phase of the reference relative to the live trace
+                    if !reference_buffer.is_empty() {
+                        match processing::phase_difference_deg(&waveform_buffer, &reference_buffer, sample_rate) {
+                            Some(phase) => ui.text(format!("Phase: {:.1}°", phase)),
+                            None => ui.text("Phase: --")
+                        }
+                    }
+
+                    // read the voltage at a typed in time, between two samples it's interpolated
+                    ui.set_next_item_width(100.0);
+                    ui.input_text("At Time", &mut probe_time).hint(if time_zero > 0 {"-0.5ms"} else {"1.5ms"}).build();
+                    if !probe_time.trim().is_empty() {
+                        ui.same_line();
+                        match processing::parse_si(&probe_time, "s") {
+                            Ok(seconds) => {
+                                let position = seconds / processing::scale_time(1, seconds_per_div, 1.0) + time_zero as f64;
+                                if seconds_per_div > 0.0 && position >= 0.0 && position <= (waveform_buffer.len() - 1) as f64 {
+                                    ui.text(format!("{:.3}{}", -processing::value_at(&waveform_buffer, position), voltage_per_div.unit_name));
+                                } else {
+                                    ui.text("out of capture window");
+                                }
+                            },
+                            Err(_) => ui.text("not a time")
+                        }
+                    }
+
+                    if ui.button("Reset Statistics") {
+                        vpp_history.reset();
+                        mean_history.reset();
+                        rms_history.reset();
+                        cycle_rms_history.reset();
+                    }
+                    ui.same_line();
+                    if ui.button("Copy") {
+                        ui.set_clipboard_text(measurements_clipboard_text(&measurements, &voltage_per_div));
+                    }
+                });
+        }
+
+        if show_measurement_table {
+            ui.window("Measurement Table")
+                .size([260.0,260.0], Condition::Appearing)
+                .position([300.0,150.0], Condition::Appearing)
+                .opened(&mut show_measurement_table)
+                .build(|| {
+                    let samples = measurement_slice(&waveform_buffer, draw_cursors && measure_between_cursors, cursor_a, cursor_b);
+                    let measurements = gated_measurements(samples, &raw_buffer, &time_per_div, &voltage_per_div, &table_measurements, reference_levels);
+                    let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                    let mut changed = false;
+
+                    // a row is selected by clicking it, the buttons below move or remove it
+                    if let Some(_table) = ui.begin_table_header_with_flags("measurement_table", [
+                        TableColumnSetup::new("Measurement"),
+                        TableColumnSetup::new("Value")
+                    ], TableFlags::BORDERS | TableFlags::RESIZABLE) {
+                        for (i, measurement) in table_measurements.iter().enumerate() {
+                            ui.table_next_row();
+                            ui.table_next_column();
+                            if ui.selectable_config(measurement.name()).selected(table_selected == Some(i)).span_all_columns(true).build() {
+                                table_selected = Some(i);
+                            }
+                            ui.table_next_column();
+                            ui.text(table_value_text(*measurement, &measurements, &voltage_per_div, seconds_per_div, time_in_divisions));
+                        }
+                    }
+
+                    let selected = table_selected.filter(|i| *i < table_measurements.len());
+                    ui.disabled(selected.is_none(), || {
+                        if ui.arrow_button("table_up", Direction::Up) {
+                            if let Some(i) = selected.filter(|i| *i > 0) {
+                                table_measurements.swap(i, i - 1);
+                                table_selected = Some(i - 1);
+                                changed = true;
+                            }
+                        }
+                        ui.same_line();
+                        if ui.arrow_button("table_down", Direction::Down) {
+                            if let Some(i) = selected.filter(|i| *i + 1 < table_measurements.len()) {
+                                table_measurements.swap(i, i + 1);
+                                table_selected = Some(i + 1);
+                                changed = true;
+                            }
+                        }
+                        ui.same_line();
+                        if ui.button("Remove") {
+                            if let Some(i) = selected {
+                                table_measurements.remove(i);
+                                table_selected = None;
+                                changed = true;
+                            }
+                        }
+                    });
+                    ui.same_line();
+                    // only the measurements that aren't in the table yet can be added
+                    if let Some(_combo) = ui.begin_combo("##table_add", "Add") {
+                        let missing: Vec<Measurement> = Measurement::ALL.iter().filter(|measurement| !table_measurements.contains(measurement)).copied().collect();
+                        for measurement in missing {
+                            if ui.selectable(measurement.name()) {
+                                table_measurements.push(measurement);
+                                changed = true;
+                            }
+                        }
+                    }
+
+                    // not being able to save only means the table starts with the defaults next time
+                    if changed {
+                        let _ = std::fs::write(MEASUREMENT_TABLE_FILE, processing::format_measurement_list(&table_measurements));
+                    }
+                });
+        }
+
+        if show_capture_stats {
+            ui.window("Capture Statistics")
+                .size([260.0,240.0], Condition::Appearing)
+                .position([300.0,200.0], Condition::Appearing)
+                .opened(&mut show_capture_stats)
+                .build(|| {
+                    let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+                    // changing the measurement starts over, mixing values of two measurements means nothing
+                    if let Some(_combo) = ui.begin_combo("Measurement", capture_stats_measurement.name()) {
+                        for measurement in Measurement::ALL {
+                            if ui.selectable_config(measurement.name()).selected(measurement == capture_stats_measurement).build() && measurement != capture_stats_measurement {
+                                capture_stats_measurement = measurement;
+                                capture_stats.reset();
+                            }
+                        }
+                    }
+                    if capture_stats_running {
+                        if ui.button("Stop") {
+                            capture_stats_running = false;
+                        }
+                    }
+                    else if ui.button("Start") {
+                        capture_stats_running = true;
+                    }
+                    ui.same_line();
+                    if ui.button("Reset") {
+                        capture_stats.reset();
+                    }
+                    ui.same_line();
+                    ui.text(if capture_stats_running {"Running"} else {"Stopped"});
+
+                    let format = |value: f64| if capture_stats.count() > 0 {
+                        measurement_value_text(capture_stats_measurement, value, &voltage_per_div, seconds_per_div, time_in_divisions)
+                    } else {
+                        String::from("--")
+                    };
+                    if let Some(_table) = ui.begin_table_header_with_flags("capture_stats_table", [
+                        TableColumnSetup::new("Statistic"),
+                        TableColumnSetup::new("Value")
+                    ], TableFlags::BORDERS | TableFlags::RESIZABLE) {
+                        let rows = [
+                            ("Captures", capture_stats.count().to_string()),
+                            ("Mean", format(capture_stats.mean())),
+                            ("Std Dev", format(capture_stats.std_dev())),
+                            ("Min", format(capture_stats.min())),
+                            ("Max", format(capture_stats.max()))
+                        ];
+                        for (label, value) in rows {
+                            ui.table_next_row();
+                            ui.table_next_column();
+                            ui.text(label);
+                            ui.table_next_column();
+                            ui.text(value);
+                        }
+                    }
+                });
+        }
+
+        if show_counter {
+            ui.window("Counter")
+                .size([360.0,140.0], Condition::Appearing)
+                .position([300.0,300.0], Condition::Appearing)
+                .opened(&mut show_counter)
+                .build(|| {
+                    ui.set_next_item_width(120.0);
+                    ui.combo_simple_string("Show", &mut primary_measurement, &PRIMARY_MEASUREMENTS);
+                    ui.same_line();
+                    ui.set_next_item_width(100.0);
+                    ui.slider("Size", 1.0, 8.0, &mut counter_font_scale);
+                    ui.separator();
+
+                    let samples = measurement_slice(&waveform_buffer, draw_cursors && measure_between_cursors, cursor_a, cursor_b);
+                    let measurements = gated_measurements(samples, &raw_buffer, &time_per_div, &voltage_per_div, &Measurement::ALL, reference_levels);
+                    let text = primary_measurement_text(primary_measurement, &measurements, &voltage_per_div);
+                    // only the number gets the large font, the controls above stay readable
+                    ui.set_window_font_scale(counter_font_scale);
+                    ui.text(text);
+                    ui.set_window_font_scale(1.0);
+                });
+        }
+
+        // frame times in milliseconds, recorded all the time so the graph is full when it's opened
+        frame_times.push(ui.io().delta_time * 1000.0);
+        if frame_times.len() > FRAME_TIME_HISTORY_LENGTH {
+            frame_times.remove(0);
+        }
+        if auto_sample_cap && last_cap_adjust.elapsed() >= SAMPLE_CAP_INTERVAL {
+            let recent = &frame_times[frame_times.len().saturating_sub(SAMPLE_CAP_FRAMES)..];
+            let average = recent.iter().sum::<f32>() / recent.len().max(1) as f32;
+            // back off quickly when too slow, only creep back up once there's clear headroom
+            if average > frame_time_target {
+                sample_cap = (sample_cap * 0.8).max(SAMPLE_CAP_MIN);
+            }
+            else if average < frame_time_target * 0.75 {
+                sample_cap = (sample_cap * 1.1).min(1.0);
+            }
+            last_cap_adjust = Instant::now();
+        }
+
+        if show_spectrogram {
+            ui.window("Spectrogram")
+                .size([400.0,300.0], Condition::Appearing)
+                .position([150.0,100.0], Condition::Appearing)
+                .opened(&mut show_spectrogram)
+                .build(|| {
+                    ui.set_next_item_width(120.0);
+                    ui.slider("Range (dB)", 20.0, 120.0, &mut spectrogram_range_db);
+                    ui.same_line();
+                    if ui.button("Clear") {
+                        spectrogram.clear();
+                    }
+                    ui.same_line();
+                    // the spectrum of the current capture goes next to the export file from the Export settings
+                    if ui.button("Export") {
+                        let sample_rate = processing::sample_rate(&time_per_div);
+                        // the samples are stored upside down, flip them so the phases come out right
+                        let samples: Vec<f64> = waveform_buffer.iter().map(|sample| -sample).collect();
+                        let (spectrum, phases) = processing::compute_spectrum_with_phase(&samples, sample_rate);
+                        let spectrum_path = std::path::Path::new(&export_path).with_extension("spectrum.csv");
+                        spectrum_message = if spectrum.is_empty() {
+                            String::from("Nothing to export, no capture or unknown time base")
+                        } else {
+                            match std::fs::write(&spectrum_path, os3000::csv::format_spectrum(&spectrum, &phases, sample_rate, &voltage_per_div.unit_name)) {
+                                Ok(_) => format!("Exported to {}", spectrum_path.display()),
+                                Err(e) => format!("Failed to export: {e}")
+                            }
+                        };
+                    }
+                    if !spectrum_message.is_empty() {
+                        ui.text_wrapped(&spectrum_message);
+                    }
+                    let top = spectrogram.front().map(|spectrum| (spectrum.len() - 1) as f64 * spectrogram_bin_width).unwrap_or(0.0);
+                    ui.text(format!("0Hz .. {}, newest at the top", processing::format_si(top, "Hz")));
+
+                    ui.child_window("spectrogram_plot").build(|| {
+                        let draw_list = ui.get_window_draw_list();
+                        let (window_width, window_height) = ui.window_size().into();
+                        let (win_x, win_y) = ui.window_pos().into();
+                        draw_list.add_rect([win_x, win_y], [win_x + window_width, win_y + window_height], color::ImColor32::from_rgba_f32s(background_color.x, background_color.y, background_color.z, 1.0)).filled(true).build();
+
+                        // colors are relative to the loudest bin anywhere in the history
+                        let loudest = spectrogram.iter().flatten().cloned().fold(0.0, f64::max);
+                        if loudest <= 0.0 {
+                            return;
+                        }
+                        let row_height = window_height / SPECTROGRAM_ROWS as f32;
+                        for (row, spectrum) in spectrogram.iter().enumerate() {
+                            let columns = spectrum.len().min(SPECTROGRAM_COLUMNS);
+                            let column_width = window_width / columns as f32;
+                            let y = win_y + row as f32 * row_height;
+                            for column in 0..columns {
+                                // the strongest of the merged bins, so narrow peaks don't disappear
+                                let bins = &spectrum[column * spectrum.len() / columns..((column + 1) * spectrum.len() / columns).max(column * spectrum.len() / columns + 1)];
+                                let magnitude = bins.iter().cloned().fold(0.0, f64::max);
+                                let db = 20.0 * (magnitude / loudest).max(1E-12).log10();
+                                let fraction = 1.0 + db as f32 / spectrogram_range_db;
+                                if fraction <= 0.0 {
+                                    continue;
+                                }
+                                let x = win_x + column as f32 * column_width;
+                                draw_list.add_rect([x, y], [x + column_width, y + row_height], heat_color(fraction)).filled(true).build();
+                            }
+                        }
+                    });
+                });
+        }
+
+        if show_ideal {
+            ui.window("Ideal Waveform")
+                .size([280.0,220.0], Condition::Appearing)
+                .position([250.0,150.0], Condition::Appearing)
+                .opened(&mut show_ideal)
+                .build(|| {
+                    ui.radio_button("Sine", &mut ideal_shape, IdealShape::Sine);
+                    ui.same_line();
+                    ui.radio_button("Square", &mut ideal_shape, IdealShape::Square);
+                    ui.same_line();
+                    ui.radio_button("Triangle", &mut ideal_shape, IdealShape::Triangle);
+                    let sample_rate = processing::sample_rate(&time_per_div);
+                    let samples: Vec<f64> = waveform_buffer.iter().map(|sample| -sample).collect();
+                    if ui.checkbox("Auto Fit", &mut ideal_auto_fit) && ideal_auto_fit {
+                        if let Some(fit) = processing::fit_ideal(&samples, sample_rate) {
+                            ideal_fit = fit;
+                        }
+                    }
+                    ui.disabled(ideal_auto_fit, || {
+                        ui.input_scalar("Frequency (Hz)", &mut ideal_fit.frequency).display_format("%.3f").build();
+                        ui.input_scalar(format!("Amplitude ({})", voltage_per_div.unit_name), &mut ideal_fit.amplitude).step(0.1).display_format("%.3f").build();
+                        ui.input_scalar(format!("Offset ({})", voltage_per_div.unit_name), &mut ideal_fit.offset).step(0.1).display_format("%.3f").build();
+                        ui.slider("Phase (deg)", 0.0, 360.0, &mut ideal_fit.phase);
+                    });
+                    ideal_fit.frequency = ideal_fit.frequency.max(0.0);
+                    // how far the capture is from the ideal shape, distortion and clipping both show up here
+                    if !samples.is_empty() {
+                        let ideal = processing::ideal_waveform(ideal_shape, &ideal_fit, sample_rate, samples.len());
+                        let deviation: Vec<f64> = samples.iter().zip(&ideal).map(|(sample, ideal)| sample - ideal).collect();
+                        ui.text(format!("Deviation: {:.3}{} RMS", processing::rms(&deviation), voltage_per_div.unit_name));
+                    }
+                });
+        }
+
+        if show_performance {
+            ui.window("Performance")
+                .size([320.0,200.0], Condition::Appearing)
+                .position([300.0,400.0], Condition::Appearing)
+                .opened(&mut show_performance)
+                .build(|| {
+                    let average = frame_times.iter().sum::<f32>() / frame_times.len() as f32;
+                    let slowest = frame_times.iter().cloned().fold(0.0, f32::max);
+                    let fastest = frame_times.iter().cloned().fold(f32::MAX, f32::min);
+                    ui.text(format!("FPS: {:.0}  Avg: {:.0}  Max: {:.0}", ui.io().framerate, 1000.0 / average, 1000.0 / fastest));
+                    ui.text(format!("Frame: {:.2}ms  Max: {:.2}ms", frame_times.last().unwrap_or(&0.0), slowest));
+                    ui.plot_lines("##frame_times", &frame_times)
+                        .scale_min(0.0)
+                        .graph_size([300.0, 60.0])
+                        .build();
+                    if ui.checkbox("Auto Sample Cap", &mut auto_sample_cap) {
+                        sample_cap = 1.0;
+                        last_cap_adjust = Instant::now();
+                    }
+                    ui.disabled(!auto_sample_cap, || {
+                        ui.slider("Target (ms)", 5.0, 50.0, &mut frame_time_target);
+                    });
+                    ui.checkbox("Batch Lines", &mut batch_lines);
+                    ui.same_line();
+                    ui.text(format!("Trace: {:.3}ms", trace_draw_time));
+                    if auto_sample_cap && sample_cap < 1.0 {
+                        ui.text_colored([1.0, 1.0, 0.0, 1.0], format!("Capped at {} / {} samples",
+                            capped_samples(interpol_samples, sample_cap, INTERPOL_SAMPLES_RANGE.0),
+                            capped_samples(interpol2_samples, sample_cap, INTERPOL2_SAMPLES_RANGE.0)));
+                    }
+                });
+        }
+
+        if show_link_stats {
+            let stats = shared.lock().map(|state| state.link_stats.clone()).unwrap_or_default();
+            let elapsed = byte_rate_sample.1.elapsed().as_secs_f64();
+            if elapsed >= 1.0 {
+                byte_rate = stats.bytes_received.saturating_sub(byte_rate_sample.0) as f64 / elapsed;
+                byte_rate_sample = (stats.bytes_received, Instant::now());
+            }
+            ui.window("Link Stats")
+                .size([300.0,240.0], Condition::Appearing)
+                .position([300.0,150.0], Condition::Appearing)
+                .opened(&mut show_link_stats)
+                .build(|| {
+                    ui.text(format!("Received: {:.0} B/s, {} bytes total", byte_rate, stats.bytes_received));
+                    let success_rate = if stats.captures_attempted > 0 {stats.captures_succeeded as f32 / stats.captures_attempted as f32 * 100.0} else {0.0};
+                    ui.text(format!("Captures: {} of {} succeeded ({:.0}%)", stats.captures_succeeded, stats.captures_attempted, success_rate));
+                    ui.separator();
+                    ui.text(format!("S1 failures: {}", stats.s1_failures));
+                    ui.text(format!("Ro failures: {}", stats.ro_failures));
+                    ui.text(format!("Ri failures: {}", stats.ri_failures));
+                    ui.text(format!("Wi failures: {}", stats.wi_failures));
+                    ui.text(format!("Short reads: {}", stats.short_reads));
+                    ui.text(format!("Timeouts: {}", stats.timeouts));
+                    ui.text(format!("Empty channel: {}", stats.empty_channels));
+                    if ui.button("Reset") {
+                        if let Ok(mut state) = shared.lock() {
+                            state.link_stats = LinkStats::default();
+                        }
+                        byte_rate_sample = (0, Instant::now());
+                    }
+                });
+        }
+
+        if show_conditions {
+            ui.window("Scope Settings")
+                .size([300.0,220.0], Condition::Appearing)
+                .position([300.0,100.0], Condition::Appearing)
+                .opened(&mut show_conditions)
+                .build(|| {
+                    if let Some(conditions) = &capture_conditions {
+                        ui.text(format!("Channel: {}", channel));
+                        ui.text(format!("Volts/div: {}{}", conditions.voltage_per_div.value, conditions.voltage_per_div.unit_name));
+                        ui.text(format!("Time/div: {}{}", conditions.time_per_div.value, conditions.time_per_div.unit_name));
+                        ui.separator();
+                        // the remaining fields aren't decoded, so list them as the scope sent them
+                        for (i, field) in conditions.fields.iter().enumerate() {
+                            ui.text(format!("{:2}: {}", i, field));
+                        }
+                    }
+                });
+        }
+
+        if show_diagnostics {
+            ui.window("Diagnostics")
+                .size([360.0,200.0], Condition::Appearing)
+                .position([300.0,100.0], Condition::Appearing)
+                .opened(&mut show_diagnostics)
+                .build(|| {
+                    if let Some(report) = &diagnostics_report {
+                        if let Some(_table) = ui.begin_table_header("diagnostics_table", [
+                            TableColumnSetup::new("Step"),
+                            TableColumnSetup::new("Time"),
+                            TableColumnSetup::new("Result")
+                        ]) {
+                            for step in &report.steps {
+                                ui.table_next_row();
+                                ui.table_next_column();
+                                ui.text(&step.name);
+                                ui.table_next_column();
+                                ui.text(format!("{} ms", step.duration.as_millis()));
+                                ui.table_next_column();
+                                match &step.result {
+                                    Ok(_) => ui.text("OK"),
+                                    Err(e) => ui.text_colored([1.0, 0.3, 0.3, 1.0], format!("{e}"))
+                                }
+                            }
+                        }
+                    }
+                });
+        }
+
         // receive data from the data capture thread
-        if let Ok(a) = waveform_rx.try_recv() {
-            if a.waveform_data.len() > 0 {
+        let responses: Vec<ScopeResponse> = shared.lock().map(|mut state| state.responses.drain(..).collect()).unwrap_or_default();
+        for a in responses {
+            if let Some(report) = a.diagnostics {
+                diagnostics_report = Some(report);
+                show_diagnostics = true;
+            }
+            // a conditions only read has no samples, just show what the scope is set to
+            if a.waveform_data.is_empty() && !a.capture_conditions.is_empty() {
+                if let Ok(conditions) = CaptureConditions::parse(&a.capture_conditions) {
+                    // the live trace on screen was captured at another scale, recordings and the scrollback keep theirs
+                    let live = last_good_capture.is_some() && replay.is_empty() && scrollback_view.is_none();
+                    if live && !(same_scale(&conditions.time_per_div, &time_per_div) && same_scale(&conditions.voltage_per_div, &voltage_per_div)) {
+                        if rescale_on_change {
+                            waveform_buffer = rescale_capture(&waveform_buffer, (&time_per_div, &voltage_per_div), (&conditions.time_per_div, &conditions.voltage_per_div));
+                        }
+                        else {
+                            waveform_buffer = vec![0.0f64; waveform_buffer.len().max(MIN_WAVEFORM_SAMPLES)];
+                            settings_changed = true;
+                        }
+                        time_per_div = conditions.time_per_div.clone();
+                        voltage_per_div = conditions.voltage_per_div.clone();
+                        // none of these match the new scale anymore
+                        raw_buffer.clear();
+                        average_buffer.clear();
+                        averaged_captures = 0;
+                        afterglow.clear();
+                        stabilize_reference.clear();
+                    }
+                    capture_conditions = Some(conditions);
+                    show_conditions = true;
+                }
+            }
+            // during a replay the live captures are dropped, the scrubber decides what is shown
+            if a.waveform_data.len() > 0 && replay.is_empty() {
+                // in normal and single mode captures without a trigger are thrown away
+                if trigger_mode != TriggerMode::Auto && !capture_once {
+                    if trigger_index(&a.waveform_data, &trigger).is_none() {
+                        continue;
+                    }
+                    triggered = true;
+                }
+                // stop before anything below can skip the rest, e.g. while the scrollback is open
+                if trigger_mode == TriggerMode::Single || capture_once {
+                    do_capture = false;
+                }
+
+                // every capture goes into the scrollback, the oldest one falls out, the baudrate only matters in files
+                let record = CaptureRecord {channel, baudrate: 0, voltage_per_div: a.voltage_per_div.clone(), time_per_div: a.time_per_div.clone(), samples: a.waveform_data.clone()};
+                scrollback.push_back((Instant::now(), record));
+                if scrollback.len() > scrollback_length {
+                    scrollback.pop_front();
+                    // keep showing the same capture while scrolled back, unless that was the one that fell out
+                    scrollback_shown = if scrollback_view == Some(0) {None} else {scrollback_shown.map(|position| position.saturating_sub(1))};
+                    scrollback_view = scrollback_view.map(|position| position.saturating_sub(1));
+                }
+                if scrollback_view.is_some() {
+                    continue;
+                }
+
+                // a knob was turned during the capture, the ones before it can't be averaged, overlaid or aligned with it
+                let scale_changed = last_good_capture.is_some() && !(same_scale(&a.time_per_div, &time_per_div) && same_scale(&a.voltage_per_div, &voltage_per_div));
+                if scale_changed {
+                    average_buffer.clear();
+                    averaged_captures = 0;
+                    afterglow.clear();
+                    stabilize_reference.clear();
+                }
+
+                // measure the time between two successful captures
+                let now = Instant::now();
+                if let Some(last) = last_capture_time {
+                    capture_interval = Some(now - last);
+                }
+                last_capture_time = Some(now);
+                last_good_capture = Some(now);
+                trace_stale = false;
+                settings_changed = false;
+
                 time_per_div = a.time_per_div;
                 voltage_per_div = a.voltage_per_div;
-                waveform_buffer = a.waveform_data;
+                previous_buffer = std::mem::replace(&mut waveform_buffer, a.waveform_data);
+                if recording {
+                    let baudrate = config_mutex.lock().map(|config| config.baudrate).unwrap_or_default();
+                    let record = CaptureRecord {channel, baudrate, voltage_per_div: voltage_per_div.clone(), time_per_div: time_per_div.clone(), samples: waveform_buffer.clone()};
+                    let result = std::fs::OpenOptions::new().create(true).append(true).open(&record_path)
+                        .and_then(|mut file| file.write_all(&format::write_capture(&record)));
+                    match result {
+                        Ok(_) => recorded_captures += 1,
+                        Err(e) => {
+                            record_message = format!("Recording stopped: {e}");
+                            recording = false;
+                        }
+                    }
+                }
+                if draw_afterglow && !scale_changed {
+                    afterglow.push_front(previous_buffer.clone());
+                    afterglow.truncate(afterglow_traces);
+                }
+                extra_traces = a.extra_traces;
+                raw_buffer = a.raw_data;
+                heatmap_new_trace = draw_heatmap;
+
+                // move the view along with the signal so repetitive signals stand still
+                if stabilize {
+                    if stabilize_reference.len() != waveform_buffer.len() {
+                        stabilize_reference = waveform_buffer.clone();
+                        stabilize_shift = 0;
+                    }
+                    else if !display_frame.interp_data.is_empty() {
+                        let shift = processing::align_to_reference(&waveform_buffer, &stabilize_reference);
+                        // the shift is in raw samples, index_start counts drawn samples
+                        let ratio = display_frame.interp_data.len() as f64 / waveform_buffer.len() as f64;
+                        let delta = ((shift - stabilize_shift) as f64 * ratio).round() as isize;
+                        index_start = (index_start as isize + delta).max(0) as usize;
+                        stabilize_shift = shift;
+                    }
+                }
+                // stabilizing moves the view on its own, the trigger position would fight it
+                if show_trigger_point {
+                    trigger_sample = trigger_index(&waveform_buffer, &trigger);
+                    align_trigger = !stabilize;
+                }
+                if let Ok(conditions) = CaptureConditions::parse(&a.capture_conditions) {
+                    capture_conditions = Some(conditions);
+                }
+
+                if average_captures {
+                    // weigh the first captures more until enough have been collected
+                    averaged_captures += 1;
+                    processing::accumulate_average(&mut average_buffer, &waveform_buffer, averaged_captures.min(average_count));
+                    waveform_buffer = average_buffer.clone();
+                }
+                if interpol2_auto {
+                    interpol2_samples = auto_interpol2_samples(waveform_buffer.len());
+                }
+
+                // track how much the measurements move between captures
+                let samples = measurement_slice(&waveform_buffer, draw_cursors && measure_between_cursors, cursor_a, cursor_b);
+                let measurements = gated_measurements(samples, &raw_buffer, &time_per_div, &voltage_per_div, &Measurement::ALL, reference_levels);
+                if let Some(vpp) = measurements.vpp {vpp_history.push(vpp);}
+                if let Some(mean) = measurements.mean {mean_history.push(mean);}
+                if let Some(vrms) = measurements.vrms {rms_history.push(vrms);}
+                if let Some(cycle_rms) = measurements.cycle_rms {cycle_rms_history.push(cycle_rms);}
+                // captures where the measurement couldn't be taken are left out of the statistics
+                if capture_stats_running {
+                    if let Some(value) = capture_stats_measurement.value(&measurements) {capture_stats.push(value);}
+                }
+
+                // slice the new capture on its edges and add it to the eye diagram
+                if show_eye {
+                    let (min, max) = waveform_buffer.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)));
+                    let level = (min + max) / 2.0;
+                    if eye_auto_period {
+                        if let Some(period) = processing::estimate_unit_interval(&waveform_buffer, level) {
+                            eye_period = period;
+                        }
+                    }
+                    eye_slices.extend(processing::slice_on_trigger(&waveform_buffer, level, eye_period, trigger.holdoff_samples));
+
+                    // drop the oldest slices
+                    if eye_slices.len() > eye_persistence {
+                        let excess = eye_slices.len() - eye_persistence;
+                        eye_slices.drain(0..excess);
+                    }
+                }
+
+                // fit the ideal waveform to the new capture
+                if show_ideal && ideal_auto_fit {
+                    let samples: Vec<f64> = waveform_buffer.iter().map(|sample| -sample).collect();
+                    if let Some(fit) = processing::fit_ideal(&samples, processing::sample_rate(&time_per_div)) {
+                        ideal_fit = fit;
+                    }
+                }
+
+                // one more row for the spectrogram, the oldest one scrolls out at the bottom
+                if show_spectrogram {
+                    let (spectrum, bin_width) = processing::compute_spectrum(&waveform_buffer, processing::sample_rate(&time_per_div));
+                    if !spectrum.is_empty() {
+                        spectrogram.push_front(spectrum);
+                        spectrogram.truncate(SPECTROGRAM_ROWS);
+                        spectrogram_bin_width = bin_width;
+                    }
+                }
             }
             
-            // TODO continous capture
-            if single_capture {
+            // single mode stops at the first response that is kept, storing or reading the settings is only ever done once
+            if trigger_mode == TriggerMode::Single || capture_once || store_channel.is_some() || read_settings {
                 do_capture = false;
             }
+            store_channel = None;
+            read_settings = false;
         }
-
-        //ui.text(format!("{}", ui.io().framerate));
     });
+
+    // the event loop only returns once the window is closed
+    shutdown.store(true, Ordering::SeqCst);
+    if capture_thread.join().is_err() {
+        eprintln!("Capture thread panicked");
+    }
 }
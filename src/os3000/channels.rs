@@ -7,8 +7,32 @@ pub enum Channel {
     SAVE2 = 4
 }
 
+impl Channel {
+    pub fn index(&self) -> usize {
+        // position in per-channel arrays, starting at 0
+        *self as usize - 1
+    }
+
+    pub fn from_number(number: u8) -> Option<Channel> {
+        // the channel numbers as the scope's commands use them
+        match number {
+            1 => Some(Self::DISPLAY1),
+            2 => Some(Self::DISPLAY2),
+            3 => Some(Self::SAVE1),
+            4 => Some(Self::SAVE2),
+            _ => None
+        }
+    }
+}
+
 impl std::fmt::Display for Channel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        let a = match *self {
+            Self::DISPLAY1  => "Display 1",
+            Self::DISPLAY2  => "Display 2",
+            Self::SAVE1     => "Save 1",
+            Self::SAVE2     => "Save 2"
+        };
+        write!(f, "{a}")
     }    
 }
@@ -1,4 +1,20 @@
 #![allow(dead_code)]
 
+use super::processing;
+use super::ValueUnitPair;
 
+#[derive(Clone, Default)]
+pub struct CaptureConditions {
+    pub fields: Vec<String>,
+    pub time_per_div: ValueUnitPair,
+    pub voltage_per_div: ValueUnitPair
+}
 
+impl CaptureConditions {
+    pub fn parse(condition_string: &String) -> Result<CaptureConditions, ()> {
+        // the Ro response is a comma separated list of the measurement conditions, terminated by a CR
+        let (time_per_div, voltage_per_div) = processing::get_scale_units(condition_string)?;
+        let fields = condition_string.trim_end().split(",").map(|field| field.trim().to_string()).collect();
+        Ok(CaptureConditions{fields, time_per_div, voltage_per_div})
+    }
+}
@@ -0,0 +1,155 @@
+use super::processing::{self, MeasurementSet};
+
+// what the first column of an exported waveform counts
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeColumn {
+    Seconds,
+    Samples,
+    Divisions
+}
+
+#[derive(Clone, Copy)]
+pub struct CsvOptions {
+    pub time_column: TimeColumn,
+    // parse_csv reads all of these back
+    pub delimiter: char,
+    // digits after the decimal point, None writes as many as it takes to read the exact value back
+    pub precision: Option<usize>
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {time_column: TimeColumn::Seconds, delimiter: ',', precision: None}
+    }
+}
+
+fn format_number(value: f64, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => format!("{value:.precision$}"),
+        None => value.to_string()
+    }
+}
+
+pub fn parse_csv(text: &str) -> Result<Vec<(f64, f64)>, ()> {
+    // reads (time, voltage) pairs, a header line and empty lines are skipped
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let columns: Vec<&str> = line.split([',', ';', '\t']).map(|column| column.trim()).collect();
+        if columns.len() < 2 {
+            return Err(());
+        }
+        match (columns[0].parse::<f64>(), columns[1].parse::<f64>()) {
+            (Ok(time), Ok(voltage)) => points.push((time, voltage)),
+            // the first line is allowed to be a header
+            _ if i == 0 => continue,
+            _ => return Err(())
+        }
+    }
+    if points.len() < 2 {
+        return Err(());
+    }
+    // the resampling needs the points in order
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(points)
+}
+
+pub fn resample_points(points: &[(f64, f64)], seconds_per_div: f64, num_samples: usize) -> (Vec<f64>, bool) {
+    // resamples the points onto the time base of a capture, 100 samples per division like scale_time
+    // returns the samples and whether the imported data overlaps the capture at all
+    let mut samples: Vec<f64> = Vec::with_capacity(num_samples);
+    let (first, last) = (points[0].0, points[points.len() - 1].0);
+    let capture_end = processing::scale_time(num_samples - 1, seconds_per_div, 1.0);
+    let overlaps = first <= capture_end && last >= 0.0;
+
+    let mut segment = 0;
+    for i in 0..num_samples {
+        let time = processing::scale_time(i, seconds_per_div, 1.0);
+        // hold the edge values outside of the imported span
+        if time <= first {
+            samples.push(points[0].1);
+            continue;
+        }
+        if time >= last {
+            samples.push(points[points.len() - 1].1);
+            continue;
+        }
+        while points[segment + 1].0 < time {
+            segment += 1;
+        }
+        let (t0, v0) = points[segment];
+        let (t1, v1) = points[segment + 1];
+        if t1 == t0 {
+            samples.push(v0);
+        }
+        else {
+            samples.push(v0 + (v1 - v0) * (time - t0) / (t1 - t0));
+        }
+    }
+    (samples, overlaps)
+}
+
+pub fn format_waveform(samples: &[f64], seconds_per_div: f64, unit: &str, options: &CsvOptions, zero: usize) -> String {
+    // writes (time, voltage) rows that parse_csv can read back, samples are stored upside down
+    // in samples or divisions the time column counts those instead, parse_csv would read them as seconds
+    // the time counts from sample zero, e.g. the trigger point
+    let delimiter = options.delimiter;
+    let time_header = match options.time_column {
+        TimeColumn::Seconds => "time (s)",
+        TimeColumn::Samples => "sample",
+        TimeColumn::Divisions => "time (div)"
+    };
+    let mut text = format!("{time_header}{delimiter}voltage ({unit})\n");
+    for (i, sample) in samples.iter().enumerate() {
+        let time = match options.time_column {
+            TimeColumn::Seconds => format_number(processing::time_since(i, zero, seconds_per_div), options.precision),
+            TimeColumn::Samples => (i as isize - zero as isize).to_string(),
+            TimeColumn::Divisions => format_number(processing::time_since(i, zero, 1.0), options.precision)
+        };
+        text.push_str(&format!("{time}{delimiter}{}\n", format_number(-sample, options.precision)));
+    }
+    text
+}
+
+pub fn format_spectrum(spectrum: &[(f64, f64)], phases: &[f64], sample_rate: f64, unit: &str) -> String {
+    // (frequency, magnitude in dB relative to one unit, phase) rows, the header comments say how the spectrum was taken
+    // bins without any amplitude are written at -240dB instead of -inf so other tools can still parse them
+    let mut text = format!("# window: {}, sample rate: {} Hz, bins: {}\n", processing::SPECTRUM_WINDOW, sample_rate, spectrum.len());
+    text.push_str(&format!("frequency (Hz),magnitude (dB{}),phase (deg)\n", unit));
+    for ((frequency, magnitude), phase) in spectrum.iter().zip(phases) {
+        text.push_str(&format!("{},{},{}\n", frequency, 20.0 * magnitude.max(1E-12).log10(), phase));
+    }
+    text
+}
+
+pub fn format_measurements(measurements: &MeasurementSet, options: &CsvOptions) -> String {
+    // one measurement per row, measurements that couldn't be taken are left empty
+    // written with the same delimiter and precision as the waveform it goes with
+    let optional = |value: Option<f64>| value.map(|value| format_number(value, options.precision)).unwrap_or_default();
+    let unit = measurements.unit.as_str();
+    let rows = [
+        ("vpp", optional(measurements.vpp), unit),
+        ("vrms", optional(measurements.vrms), unit),
+        ("cycle_rms", optional(measurements.cycle_rms), unit),
+        ("mean", optional(measurements.mean), unit),
+        ("frequency", optional(measurements.frequency), "Hz"),
+        ("period", optional(measurements.period), "s"),
+        ("period_jitter", optional(measurements.period_jitter), "s"),
+        ("rise_time", optional(measurements.rise_time), "s"),
+        ("fall_time", optional(measurements.fall_time), "s"),
+        ("duty_cycle", optional(measurements.duty_cycle), ""),
+        ("mark", optional(measurements.mark_space.map(|mark_space| mark_space.mark)), "s"),
+        ("space", optional(measurements.mark_space.map(|mark_space| mark_space.space)), "s"),
+        ("crest_factor", optional(measurements.crest_factor), ""),
+        ("form_factor", optional(measurements.form_factor), "")
+    ];
+    let delimiter = options.delimiter;
+    let mut text = format!("measurement{delimiter}value{delimiter}unit\n");
+    for (name, value, unit) in rows {
+        text.push_str(&format!("{name}{delimiter}{value}{delimiter}{unit}\n"));
+    }
+    text
+}
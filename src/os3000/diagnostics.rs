@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use super::errors::OscilloscopeError;
+
+#[derive(Clone)]
+pub struct DiagnosticStep {
+    pub name: String,
+    pub duration: Duration,
+    pub result: Result<(), OscilloscopeError>
+}
+
+#[derive(Clone, Default)]
+pub struct DiagnosticsReport {
+    pub steps: Vec<DiagnosticStep>
+}
+
+impl DiagnosticsReport {
+    pub fn push(&mut self, name: &str, duration: Duration, result: Result<(), OscilloscopeError>) {
+        self.steps.push(DiagnosticStep{name: name.to_string(), duration, result});
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.steps.iter().all(|step| step.result.is_ok())
+    }
+}
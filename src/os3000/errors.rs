@@ -6,7 +6,14 @@ pub enum OscilloscopeError {
     S1Failure,
     WriteError,
     RiError,
-    RoError
+    RoError,
+    EmptyChannel,
+    NoResponse,
+    // the Ri header doesn't echo the command that was sent, so the samples can't be trusted either
+    HeaderMismatch,
+    // a Wi data byte is a CR, which would end the frame early, the OS3000 has no way of escaping it
+    CarriageReturnInData,
+    LengthMismatch {expected: usize, received: usize}
 }
 
 impl Display for OscilloscopeError {
@@ -15,7 +22,14 @@ impl Display for OscilloscopeError {
             Self::RiError  => "capture error",
             Self::S1Failure     => "S1 failure",
             Self::WriteError    => "write error",
-            Self::RoError=> "measurement condition error"
+            Self::RoError=> "measurement condition error",
+            Self::EmptyChannel  => "channel is empty",
+            Self::NoResponse    => "no response",
+            Self::HeaderMismatch => "Ri header doesn't match the command",
+            Self::CarriageReturnInData => "Wi data contains a CR byte",
+            Self::LengthMismatch {expected, received} => {
+                return write!(f, "expected {expected} bytes, received {received}");
+            }
         };
         write!(f, "{a}")
     }
@@ -0,0 +1,179 @@
+use super::channels::Channel;
+use super::ValueUnitPair;
+
+// every record starts with these bytes, followed by the version
+pub const MAGIC: [u8; 4] = *b"OSCP";
+pub const VERSION: u16 = 1;
+
+#[derive(Clone)]
+pub struct CaptureRecord {
+    pub channel:         Channel,
+    pub baudrate:        u32,
+    pub voltage_per_div: ValueUnitPair,
+    pub time_per_div:    ValueUnitPair,
+    pub samples:         Vec<f64>
+}
+
+fn write_unit(bytes: &mut Vec<u8>, unit: &ValueUnitPair) {
+    // value, multiplier and the unit name with a one byte length in front
+    bytes.extend_from_slice(&unit.value.to_le_bytes());
+    bytes.extend_from_slice(&unit.unit_mult.to_le_bytes());
+    let name = unit.unit_name.as_bytes();
+    let length = name.len().min(u8::MAX as usize);
+    bytes.push(length as u8);
+    bytes.extend_from_slice(&name[..length]);
+}
+
+pub fn write_capture(record: &CaptureRecord) -> Vec<u8> {
+    // all numbers are little endian, the samples are written as they are stored (upside down)
+    let mut bytes: Vec<u8> = Vec::with_capacity(64 + record.samples.len() * 8);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.push(record.channel as u8);
+    bytes.extend_from_slice(&record.baudrate.to_le_bytes());
+    write_unit(&mut bytes, &record.voltage_per_div);
+    write_unit(&mut bytes, &record.time_per_div);
+    bytes.extend_from_slice(&(record.samples.len() as u32).to_le_bytes());
+    for sample in &record.samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(self: &mut Self, length: usize) -> Result<&'a [u8], ()> {
+        // running out of bytes means the file was cut off
+        let end = self.position.checked_add(length).ok_or(())?;
+        let taken = self.bytes.get(self.position..end).ok_or(())?;
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn u8(self: &mut Self) -> Result<u8, ()> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(self: &mut Self) -> Result<u16, ()> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().map_err(|_| ())?))
+    }
+
+    fn u32(self: &mut Self) -> Result<u32, ()> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().map_err(|_| ())?))
+    }
+
+    fn f64(self: &mut Self) -> Result<f64, ()> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().map_err(|_| ())?))
+    }
+
+    fn unit(self: &mut Self) -> Result<ValueUnitPair, ()> {
+        let value = self.f64()?;
+        let unit_mult = self.f64()?;
+        let length = self.u8()? as usize;
+        let unit_name = String::from_utf8(self.take(length)?.to_vec()).map_err(|_| ())?;
+        Ok(ValueUnitPair{value, unit_mult, unit_name})
+    }
+}
+
+fn read_record(reader: &mut Reader) -> Result<CaptureRecord, ()> {
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(());
+    }
+    // version 1 is the only one so far, newer versions have to add their own branch here
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(());
+    }
+    let channel = Channel::from_number(reader.u8()?).ok_or(())?;
+    let baudrate = reader.u32()?;
+    let voltage_per_div = reader.unit()?;
+    let time_per_div = reader.unit()?;
+    let count = reader.u32()? as usize;
+    // check the length up front instead of allocating whatever a broken header says
+    if reader.bytes.len() - reader.position < count * 8 {
+        return Err(());
+    }
+    let samples = (0..count).map(|_| reader.f64()).collect::<Result<Vec<f64>, ()>>()?;
+    Ok(CaptureRecord{channel, baudrate, voltage_per_div, time_per_div, samples})
+}
+
+pub fn read_capture(bytes: &[u8]) -> Result<CaptureRecord, ()> {
+    read_record(&mut Reader{bytes, position: 0})
+}
+
+pub fn read_captures(bytes: &[u8]) -> Result<Vec<CaptureRecord>, ()> {
+    // a log is just records written one after another
+    let mut reader = Reader{bytes, position: 0};
+    let mut records: Vec<CaptureRecord> = Vec::new();
+    while reader.position < bytes.len() {
+        records.push(read_record(&mut reader)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(channel: Channel, samples: Vec<f64>) -> CaptureRecord {
+        CaptureRecord {
+            channel,
+            baudrate: 19200,
+            voltage_per_div: ValueUnitPair{value: 500.0, unit_mult: 1E3, unit_name: String::from("mV")},
+            time_per_div: ValueUnitPair{value: 2.0, unit_mult: 1E6, unit_name: String::from("us")},
+            samples
+        }
+    }
+
+    fn assert_same(read: &CaptureRecord, written: &CaptureRecord) {
+        assert!(read.channel == written.channel);
+        assert_eq!(read.baudrate, written.baudrate);
+        for (read, written) in [(&read.voltage_per_div, &written.voltage_per_div), (&read.time_per_div, &written.time_per_div)] {
+            assert_eq!(read.value, written.value);
+            assert_eq!(read.unit_mult, written.unit_mult);
+            assert_eq!(read.unit_name, written.unit_name);
+        }
+        assert_eq!(read.samples, written.samples);
+    }
+
+    #[test]
+    fn round_trip() {
+        let written = record(Channel::DISPLAY2, vec![1.5, -0.25, 3.0E-9, f64::MAX]);
+        assert_same(&read_capture(&write_capture(&written)).unwrap(), &written);
+    }
+
+    #[test]
+    fn round_trip_log() {
+        let written = [record(Channel::DISPLAY1, vec![0.0; 1000]), record(Channel::SAVE1, Vec::new()), record(Channel::SAVE2, vec![-7.0, 7.0])];
+        let bytes: Vec<u8> = written.iter().flat_map(write_capture).collect();
+        let read = read_captures(&bytes).unwrap();
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(&written) {
+            assert_same(read, written);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = write_capture(&record(Channel::DISPLAY1, vec![1.0, 2.0, 3.0]));
+        for length in 0..bytes.len() {
+            assert!(read_capture(&bytes[..length]).is_err(), "{length} bytes read");
+        }
+        // a log cut off in the second record fails as a whole
+        let mut log = bytes.clone();
+        log.extend_from_slice(&bytes[..bytes.len() - 1]);
+        assert!(read_captures(&log).is_err());
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = write_capture(&record(Channel::DISPLAY1, vec![1.0]));
+        bytes[0] = b'X';
+        assert!(read_capture(&bytes).is_err());
+        assert!(read_captures(&bytes).is_err());
+    }
+}
@@ -1,28 +1,132 @@
 #![allow(dead_code, unused_mut)]
 use std::borrow::BorrowMut;
 use std::io::{BufRead, BufReader, Write};
-use std::{io::Read, time::Duration};
+use std::{io::Read, time::{Duration, Instant}};
 use std::thread::sleep;
-use serialport::{self,StopBits, TTYPort};
+use serialport::{self,DataBits,Parity,SerialPort,StopBits, TTYPort};
 
 pub mod channels;
 pub mod errors;
 pub mod condition;
 pub mod processing;
+pub mod diagnostics;
+pub mod csv;
+pub mod svg;
+pub mod format;
 
 use channels::Channel;
 use errors::OscilloscopeError;
+use diagnostics::DiagnosticsReport;
+use processing::SampleFormat;
 
 
+// the Ri response starts with a header before the sample bytes, firmware revisions differ in its length
+pub const RI_HEADER_LENGTH: usize = 14;
+// the header echoes the command, anything longer than this isn't a header
+const MAX_RI_HEADER_LENGTH: usize = 32;
+// the OS3000 records 1000 points per channel
+pub const DEFAULT_RECORD_LENGTH: u32 = 1000;
+// the Ri addresses have four digits, a deep record can't reach past this
+pub const MAX_RI_ADDRESS: u32 = 9999;
+// reads per Ri frame, the whole frame has to arrive within the port timeout regardless
+pub const DEFAULT_RI_READ_ATTEMPTS: u32 = 8;
+
+// the Ro response is usually this long including the CR, but the padding of the fields varies a little
+pub const RO_RESPONSE_LENGTH: usize = 68;
+const RO_LENGTH_TOLERANCE: usize = 2;
+// a response with fewer fields isn't a condition string, even if processing::get_scale_units might find units in it
+const RO_MIN_FIELDS: usize = 12;
+
+// the longest response that has to arrive within one read, the header, the samples and the CR of a full Ri
+const MAX_RESPONSE_BYTES: u32 = RI_HEADER_LENGTH as u32 + DEFAULT_RECORD_LENGTH + 1;
+// extra time on top of the pure transfer time, the scope takes a moment before it starts sending
+const READ_TIMEOUT_MARGIN_MS: u64 = 500;
+// resync drains the input until nothing has come in for this long, a byte takes about 35ms even at 300 baud
+const RESYNC_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub fn bits_per_byte(stopbits: StopBits, data_bits: DataBits, parity: Parity) -> u32 {
+    // a start bit, the data bits, an optional parity bit and one or two stop bits
+    let data_bits = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8
+    };
+    let parity_bits = if parity == Parity::None {0} else {1};
+    let stop_bits = if stopbits == StopBits::Two {2} else {1};
+    1 + data_bits + parity_bits + stop_bits
+}
+
+pub fn read_timeout(baud_rate: u32, bits_per_byte: u32, bytes: u32) -> Duration {
+    // time on the wire plus half of that again, so a slightly slow scope doesn't run into the timeout
+    let transfer_ms = (bytes * bits_per_byte) as u64 * 1000 / baud_rate.max(1) as u64;
+    Duration::from_millis(transfer_ms * 3 / 2 + READ_TIMEOUT_MARGIN_MS)
+}
+
+pub fn check_ro_response(response: &[u8]) -> Result<String, OscilloscopeError> {
+    // turns the Ro response into the condition string, without the CR and trailing whitespace
+    let text = String::from_utf8(response.to_vec()).map_err(|_| OscilloscopeError::RoError)?;
+    let text = text.trim_end();
+    let length_ok = response.len().abs_diff(RO_RESPONSE_LENGTH) <= RO_LENGTH_TOLERANCE;
+    // the fields are separated by commas, so a response of an odd length is still fine as long as all of them are there
+    if !length_ok && text.split(',').count() < RO_MIN_FIELDS {
+        return Err(OscilloscopeError::LengthMismatch{expected: RO_RESPONSE_LENGTH, received: response.len()});
+    }
+    Ok(text.to_string())
+}
+
 fn make_ri_command(channel: Channel, start_address:u32, end_address:u32) -> String {
     //construct command from string and parameters
     return format!("R{}({:04},{:04},B)\r", channel as u8, start_address, end_address);
 }
 
+pub fn check_ri_header(header: &[u8], channel: Channel) -> bool {
+    // the header echoes the command, so it starts with the channel that was asked for
+    header.starts_with(format!("R{}(", channel as u8).as_bytes())
+}
+
+pub fn detect_ri_header_length(frame: &[u8], sample_bytes: usize, channel: Channel) -> Option<usize> {
+    // a complete frame ends with the CR, whatever is in front of the sample bytes is the header
+    if frame.last() != Some(&0x0D) || frame.len() <= sample_bytes + 1 {
+        return None;
+    }
+    let header_length = frame.len() - sample_bytes - 1;
+    if header_length > MAX_RI_HEADER_LENGTH || !check_ri_header(&frame[..header_length], channel) {
+        return None;
+    }
+    Some(header_length)
+}
+
+pub fn ri_frame_header_length(frame: &[u8], sample_bytes: usize, channel: Channel, header_override: Option<usize>, last_header_length: usize) -> Result<usize, OscilloscopeError> {
+    // only a header length set by the user has to echo the command, otherwise the frame is taken with the usual fixed header
+    if let Some(header_length) = header_override {
+        if frame.len() != header_length + sample_bytes + 1 {
+            return Err(OscilloscopeError::LengthMismatch{expected: header_length + sample_bytes + 1, received: frame.len()});
+        }
+        if !check_ri_header(&frame[..header_length], channel) {
+            return Err(OscilloscopeError::HeaderMismatch);
+        }
+        return Ok(header_length);
+    }
+    if frame.len() == last_header_length + sample_bytes + 1 {
+        return Ok(last_header_length);
+    }
+    if frame.len() == RI_HEADER_LENGTH + sample_bytes + 1 {
+        return Ok(RI_HEADER_LENGTH);
+    }
+    // a frame of a different length is fine as long as its header echoes the command
+    detect_ri_header_length(frame, sample_bytes, channel).ok_or(OscilloscopeError::LengthMismatch{expected: last_header_length + sample_bytes + 1, received: frame.len()})
+}
+
 fn make_ro_command(channel: Channel) -> String {
     return format!("Ro({})\r", channel as u8);
 }
 
+fn make_wi_command(channel: Channel, start_address:u32, end_address:u32) -> String {
+    // same framing as the Ri command, the sample bytes follow after the CR
+    return format!("W{}({:04},{:04},B)\r", channel as u8, start_address, end_address);
+}
+
 #[derive(Clone, Default)]
 pub struct ValueUnitPair {
     pub value: f64,
@@ -30,29 +134,73 @@ pub struct ValueUnitPair {
     pub unit_name: String
 }
 
+// the scope is slow, so every command needs some time before the answer can be read
+#[derive(Clone, Copy, PartialEq)]
+pub struct CommandDelays {
+    pub after_s1_ms: u64,
+    pub before_ro_read_ms: u64,
+    pub after_ro_ms: u64,
+    pub before_ri_read_ms: u64
+}
+
+impl Default for CommandDelays {
+    fn default() -> Self {
+        CommandDelays {after_s1_ms: 500, before_ro_read_ms: 1000, after_ro_ms: 250, before_ri_read_ms: 750}
+    }
+}
+
+#[derive(Clone)]
+pub struct ChannelCapture {
+    pub channel:            Channel,
+    pub waveform_data:      Vec<f64>,
+    pub raw_data:           Vec<u8>,
+    pub time_per_div:       ValueUnitPair,
+    pub voltage_per_div:    ValueUnitPair,
+    pub capture_conditions: String
+}
+
 pub struct OscilloscopeCapture {
     pub port:               TTYPort,
     response_data:      Vec<u8>,
     command_buffer:     Vec<u8>,
-    cond_string:        String
+    cond_string:        String,
+    pub record_length:  u32,
+    pub sample_format:  SampleFormat,
+    // how many reads send_ri does before it gives up on a short frame
+    pub ri_read_attempts: u32,
+    // None detects the Ri header length from the frames
+    pub ri_header_length: Option<usize>,
+    // header length of the last Ri frame, also the guess for the next one
+    frame_header_length: usize,
+    pub delays: CommandDelays,
+    // every byte read from the port so far, for the link statistics
+    pub bytes_received: u64,
+    // the sample bytes of all blocks of the last deep record
+    deep_samples: Vec<u8>
 }
 
 impl OscilloscopeCapture {
-    pub fn new(port_name: &str, baud_rate: u32, stopbits: StopBits) -> OscilloscopeCapture {
+    pub fn new(port_name: &str, baud_rate: u32, stopbits: StopBits, data_bits: DataBits, parity: Parity) -> OscilloscopeCapture {
+        Self::open(port_name, baud_rate, stopbits, data_bits, parity).expect("Failed to open port")
+    }
+
+    pub fn open(port_name: &str, baud_rate: u32, stopbits: StopBits, data_bits: DataBits, parity: Parity) -> Result<OscilloscopeCapture, serialport::Error> {
+        // like new, but a missing or busy port is an error instead of a panic
+        // at 300 baud a full Ri takes more than half a minute, at 9600 about a second
+        let timeout = read_timeout(baud_rate, bits_per_byte(stopbits, data_bits, parity), MAX_RESPONSE_BYTES);
         let mut port: TTYPort = serialport::new(port_name, baud_rate)
         .stop_bits(stopbits)
-        .parity(serialport::Parity::None)
+        .parity(parity)
         .flow_control(serialport::FlowControl::None)
-        .data_bits(serialport::DataBits::Eight)
-        .timeout(Duration::from_millis(2000))
-        .open_native()
-        .expect("Failed to open port");
+        .data_bits(data_bits)
+        .timeout(timeout)
+        .open_native()?;
         
         let mut response_data: Vec<u8>          =    Vec::with_capacity(1015);
         let mut command_buffer: Vec<u8>         =    Vec::with_capacity(32);
         let mut cond_string: String             =    String::new();
 
-        return OscilloscopeCapture{port,response_data: response_data,command_buffer, cond_string};
+        return Ok(OscilloscopeCapture{port,response_data: response_data,command_buffer, cond_string, record_length: DEFAULT_RECORD_LENGTH, sample_format: SampleFormat::default(), ri_read_attempts: DEFAULT_RI_READ_ATTEMPTS, ri_header_length: None, frame_header_length: RI_HEADER_LENGTH, delays: CommandDelays::default(), bytes_received: 0, deep_samples: Vec::new()});
     }
 
     fn make_command(self: &mut Self, command: String) {
@@ -63,15 +211,21 @@ impl OscilloscopeCapture {
         }
     } 
 
-    fn eval_response(self: &mut Self) -> bool {
+    fn eval_response(self: &mut Self) -> Option<bool> {
+        // None if the scope didn't send anything at all, otherwise whether it acknowledged the command
         let mut response_buffer: Vec<u8> = Vec::new();
         let mut buffy = BufReader::new(self.port.borrow_mut());
         sleep(Duration::from_millis(10));
-        if let Ok(num) = buffy.read_until(0x0D, &mut response_buffer) {
+        let result = buffy.read_until(0x0D, &mut response_buffer);
+        self.bytes_received += response_buffer.len() as u64;
+        if response_buffer.is_empty() {
+            return None;
+        }
+        if let Ok(num) = result {
             //println!("Response: {}", char::from(response_buffer[0]));
-            return num == 2 && response_buffer[0] == 0x41;
+            return Some(num == 2 && response_buffer[0] == 0x41);
         }
-        false 
+        Some(false)
     }
 
     pub fn send_s1(self: &mut Self) -> Result<(), OscilloscopeError> {
@@ -81,8 +235,11 @@ impl OscilloscopeCapture {
             // clear command buffer
             self.command_buffer.clear();
             // evaluate response
-            if self.eval_response() {return Ok(());}
-            else{Err(OscilloscopeError::S1Failure)}
+            match self.eval_response() {
+                Some(true) => Ok(()),
+                Some(false) => Err(OscilloscopeError::S1Failure),
+                None => Err(OscilloscopeError::NoResponse)
+            }
         }
         else {
             self.command_buffer.clear();
@@ -99,36 +256,29 @@ impl OscilloscopeCapture {
         self.make_command(make_ro_command(channel));
 
         if let Ok(_) = self.port.write_all(&self.command_buffer) {
-            sleep(Duration::from_millis(1000));
+            sleep(Duration::from_millis(self.delays.before_ro_read_ms));
             let mut reader = BufReader::new(&mut self.port);
-            let mut local_buffer = Vec::<u8>::with_capacity(68);
-
-            if let Ok(num) = reader.read_until(0x0D, &mut local_buffer) {
-                // ensure that the received data has the required length
-                println!("{}", num);
-                if num == 68 {
-                    // convert the result into a string and store it in self.cond_string
-                    if let Ok(cond_string) = String::from_utf8(local_buffer) {
-                        self.cond_string = cond_string;
-                        // clear command buffer
-                        self.command_buffer.clear();
-                        return Ok(());
-                    }
-                    // throw an error if it fails to get the string like when a channel has no information
-                    else {
-                        return Err(OscilloscopeError::RoError);
-                    }
-                    
-
-                }
+            let mut local_buffer = Vec::<u8>::with_capacity(RO_RESPONSE_LENGTH);
 
-                else {
-                    return  Err(OscilloscopeError::S1Failure);
+            let result = reader.read_until(0x0D, &mut local_buffer);
+            self.bytes_received += local_buffer.len() as u64;
+            if let Ok(num) = result {
+                if num > 0 {
+                    // store the condition string in self.cond_string if it looks like one
+                    self.cond_string = check_ro_response(&local_buffer)?;
+                    // clear command buffer
+                    self.command_buffer.clear();
+                    return Ok(());
                 }
             }
-
+            // nothing at all came back, as opposed to a garbled response
+            if local_buffer.is_empty() {
+                return Err(OscilloscopeError::NoResponse);
+            }
+            // the response was cut off, e.g. by the timeout
+            return Err(OscilloscopeError::LengthMismatch{expected: RO_RESPONSE_LENGTH, received: local_buffer.len()});
         } 
-        Err(OscilloscopeError::RiError)
+        Err(OscilloscopeError::RoError)
     }
 
     pub fn send_ri(self: &mut Self, channel: Channel, start_address:u32, end_address:u32) -> Result<(), OscilloscopeError> {
@@ -137,26 +287,114 @@ impl OscilloscopeCapture {
         self.make_command(make_ri_command(channel, start_address, end_address));
         // write command
         if let Ok(_) = self.port.write_all(&self.command_buffer) {
-            sleep(Duration::from_millis(750));
+            sleep(Duration::from_millis(self.delays.before_ri_read_ms));
             // clear waveform buffer
             self.response_data.clear();
-            // read until we get a CR or time out
+            // header, sample bytes and the terminating CR
+            let sample_bytes = (end_address - start_address) as usize;
+            let header_length = self.ri_header_length.unwrap_or(self.frame_header_length);
+            let expected = header_length + sample_bytes + 1;
+            // a slow scope sends the frame in chunks and a sample byte can look like the CR,
+            // so keep reading until the whole frame is there or the time is up
+            let deadline = Instant::now() + self.port.timeout();
             let mut reader = BufReader::new(&mut self.port);
-            match reader.read_until(0x0D, &mut self.response_data) {
-                Ok(num) => {
-                    if num == (end_address - start_address) as usize + 15 {
-                        self.command_buffer.clear();
-                        self.response_data.pop();
-                        return Ok(());
-                    }},
-                Err(_e) => {
-                    return Err(OscilloscopeError::RiError);
+            let mut attempts = 0;
+            while self.response_data.len() < expected && attempts < self.ri_read_attempts && Instant::now() < deadline {
+                attempts += 1;
+                if let Err(_e) = reader.read_until(0x0D, &mut self.response_data) {
+                    break;
                 }
             }
+            let received = self.response_data.len();
+            self.bytes_received += received as u64;
+            if received == 0 {
+                return Err(OscilloscopeError::NoResponse);
+            }
+            let header_length = ri_frame_header_length(&self.response_data, sample_bytes, channel, self.ri_header_length, self.frame_header_length)?;
+            self.command_buffer.clear();
+            self.frame_header_length = header_length;
+            self.response_data.pop();
+            return Ok(());
         }
         Err(OscilloscopeError::RiError)
     }
     
+    pub fn send_wi(self: &mut Self, channel: Channel, data: &[u8]) -> Result<(), OscilloscopeError> {
+        // only the save channels can be written to
+        if channel != Channel::SAVE1 && channel != Channel::SAVE2 {
+            return Err(OscilloscopeError::WriteError);
+        }
+        // a CR inside the data would end the frame early, rather refuse than store something else than asked for
+        if data.contains(&0x0D) {
+            return Err(OscilloscopeError::CarriageReturnInData);
+        }
+        self.command_buffer.clear();
+        // construct Wi command, followed by the sample data and a terminating CR
+        self.make_command(make_wi_command(channel, 0, data.len() as u32));
+        self.command_buffer.extend_from_slice(data);
+        self.command_buffer.push(0x0D);
+
+        if let Ok(_) = self.port.write_all(&self.command_buffer) {
+            self.command_buffer.clear();
+            // the scope acknowledges the written data just like S1
+            return match self.eval_response() {
+                Some(true) => Ok(()),
+                Some(false) => Err(OscilloscopeError::WriteError),
+                None => Err(OscilloscopeError::NoResponse)
+            };
+        }
+        self.command_buffer.clear();
+        Err(OscilloscopeError::WriteError)
+    }
+
+    pub fn diagnose(self: &mut Self) -> DiagnosticsReport {
+        // runs every step of a capture on its own and times it, so a flaky link can be narrowed down
+        let mut report = DiagnosticsReport::default();
+
+        let start = Instant::now();
+        let s1_result = self.send_s1();
+        report.push("S1", start.elapsed(), s1_result.clone());
+
+        // nothing else will work without a successful S1
+        if s1_result.is_err() {
+            return report;
+        }
+        sleep(Duration::from_millis(self.delays.after_s1_ms));
+
+        for channel in [Channel::DISPLAY1, Channel::DISPLAY2, Channel::SAVE1, Channel::SAVE2] {
+            let start = Instant::now();
+            let result = self.send_ro(channel);
+            report.push(&format!("Ro ({channel})"), start.elapsed(), result);
+            sleep(Duration::from_millis(self.delays.after_ro_ms));
+        }
+
+        // a short Ri to test the bulk transfer without waiting for the full 1000 bytes
+        let start = Instant::now();
+        let result = self.send_ri(Channel::DISPLAY1, 0, 100);
+        report.push("Ri (100 bytes)", start.elapsed(), result);
+
+        report
+    }
+
+    pub fn condition_string(self: &Self) -> &String {
+        // the condition string from the last Ro
+        &self.cond_string
+    }
+
+    pub fn raw_samples(self: &Self) -> &[u8] {
+        // the sample bytes of the last Ri, without the header
+        let end = self.frame_header_length + self.record_length as usize;
+        if self.response_data.len() < end {
+            return &[];
+        }
+        &self.response_data[self.frame_header_length..end]
+    }
+
+    pub fn deep_raw_samples(self: &Self) -> &[u8] {
+        // the sample bytes of the last deep record, all blocks in order
+        &self.deep_samples
+    }
+
     pub fn s1_recover(self: &mut Self) {
         //eprintln!("S1 Error");
         sleep(Duration::from_secs(1));
@@ -165,7 +403,21 @@ impl OscilloscopeCapture {
         //let _ = self.port.clear(serialport::ClearBuffer::All);
         self.command_buffer.clear();
     }
-    
+
+    pub fn resync(self: &mut Self) {
+        // after a failed command the scope may still be sending, which offsets every following response
+        // wait for it to finish, then throw away everything that is left in the input buffer
+        sleep(Duration::from_millis(500));
+        let _ = self.port.clear(serialport::ClearBuffer::Input);
+        // read_to_end only stops at the timeout, the one for a whole Ri would stall for most of a minute at low baud rates
+        let timeout = self.port.timeout();
+        let _ = self.port.set_timeout(RESYNC_DRAIN_TIMEOUT);
+        let mut kill_buf:Vec<u8> = Vec::new();
+        let _ = self.port.read_to_end(&mut kill_buf);
+        let _ = self.port.set_timeout(timeout);
+        self.command_buffer.clear();
+        self.response_data.clear();
+    }
 
     #[allow(unused_assignments)]
     pub fn get_waveform_data(self: &mut Self, channel: Channel, scale: f64) -> Result<(Vec<f64>, ValueUnitPair, ValueUnitPair), OscilloscopeError> {
@@ -179,7 +431,7 @@ impl OscilloscopeCapture {
 
         
         // sleep a bit
-        sleep(Duration::from_millis(500));
+        sleep(Duration::from_millis(self.delays.after_s1_ms));
         println!("S1 Successful");
 
         // now we need to get the condition data to accurately scale the data 
@@ -192,22 +444,147 @@ impl OscilloscopeCapture {
             // it's time to actually get the waveform data, this is a _very_ unrealiable process at "high" baudrates like 9600 so it's likely to fail
             // wait a bit because the scope is pretty slow
 
-            sleep(Duration::from_millis(250));
+            sleep(Duration::from_millis(self.delays.after_ro_ms));
             println!("Ro Successful");
 
-            self.send_ri(channel, 0, 1000)?;
+            self.send_ri(channel, 0, self.record_length)?;
 
             println!("Ri Successful");
             // now we need to scale the raw waveform data correctly and turn it into a series of f64 points
-            waveform_data = processing::scale_waveform_data(&self.response_data[14..1000].to_vec(), voltage_unit.value, scale);
+            waveform_data = processing::scale_waveform_data(&self.raw_samples().to_vec(), voltage_unit.value, scale, self.sample_format);
             waveform_data = processing::unit_scale(&waveform_data, &voltage_unit);
             // now we could interpolate the data or we could do it in real time
             //waveform_data = scaling::spline_interpolate_samples(&waveform_data, num_samples, time_unit.value);
 
             Ok((waveform_data, time_unit, voltage_unit))
         }
+        // a channel without a stored waveform has no scale information
+        else if processing::is_empty_channel(&self.cond_string) {
+            return Err(OscilloscopeError::EmptyChannel);
+        }
         else {
             return Err(OscilloscopeError::RoError);
         }
     }
+
+    pub fn get_deep_waveform(self: &mut Self, channel: Channel, total_points: u32) -> Result<Vec<f64>, OscilloscopeError> {
+        self.get_deep_waveform_with_progress(channel, total_points, |_, _| {})
+    }
+
+    pub fn get_deep_waveform_with_progress(self: &mut Self, channel: Channel, total_points: u32, mut progress: impl FnMut(usize, usize)) -> Result<Vec<f64>, OscilloscopeError> {
+        // reads the memory in blocks of one record length with consecutive Ri address ranges and puts them back together
+        // progress gets the number of finished blocks and the number of blocks before every block and once at the end
+        let total_points = total_points.clamp(1, MAX_RI_ADDRESS);
+        let block_length = self.record_length.max(1);
+        let blocks = total_points.div_ceil(block_length) as usize;
+
+        // one S1 and Ro for the whole record, the scale doesn't change between the blocks
+        self.send_s1()?;
+        sleep(Duration::from_millis(self.delays.after_s1_ms));
+        self.send_ro(channel)?;
+        let Ok((_, voltage_unit)) = processing::get_scale_units(&self.cond_string) else {
+            return Err(if processing::is_empty_channel(&self.cond_string) {OscilloscopeError::EmptyChannel} else {OscilloscopeError::RoError});
+        };
+
+        self.deep_samples.clear();
+        for block in 0..blocks {
+            progress(block, blocks);
+            sleep(Duration::from_millis(self.delays.after_ro_ms));
+            let start = block as u32 * block_length;
+            let end = (start + block_length).min(total_points);
+            self.send_ri(channel, start, end)?;
+            // send_ri made sure the whole block is there
+            let header_length = self.frame_header_length;
+            self.deep_samples.extend_from_slice(&self.response_data[header_length..header_length + (end - start) as usize]);
+        }
+        progress(blocks, blocks);
+
+        // scaled in one go, so the automatic sample format sees the whole record
+        let waveform_data = processing::scale_waveform_data(&self.deep_samples, voltage_unit.value, 1.0, self.sample_format);
+        Ok(processing::unit_scale(&waveform_data, &voltage_unit))
+    }
+
+    pub fn get_all_waveforms(self: &mut Self, channels: &[Channel]) -> Result<Vec<ChannelCapture>, OscilloscopeError> {
+        // runs the whole S1, Ro, Ri sequence for every channel, one failed channel fails the whole acquisition
+        let mut captures: Vec<ChannelCapture> = Vec::with_capacity(channels.len());
+        for channel in channels {
+            let (waveform_data, time_per_div, voltage_per_div) = self.get_waveform_data(*channel, 1.0)?;
+            captures.push(ChannelCapture {
+                channel: *channel,
+                waveform_data,
+                raw_data: self.raw_samples().to_vec(),
+                time_per_div,
+                voltage_per_div,
+                capture_conditions: self.cond_string.clone()
+            });
+        }
+        Ok(captures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a condition string padded with spaces in front of the CR to the given length
+    fn ro_response(length: usize) -> Vec<u8> {
+        let mut response = String::from("0,DC,0,5ms,1,0,0,2V,0,0,0,0");
+        while response.len() < length - 1 {
+            response.push(' ');
+        }
+        response.push('\r');
+        response.into_bytes()
+    }
+
+    #[test]
+    fn ro_lengths_around_the_usual_one() {
+        for length in [67, 68, 69] {
+            let response = ro_response(length);
+            assert_eq!(response.len(), length);
+            let conditions = check_ro_response(&response).unwrap();
+            assert_eq!(conditions, "0,DC,0,5ms,1,0,0,2V,0,0,0,0");
+            let (time_per_div, voltage_per_div) = processing::get_scale_units(&conditions).unwrap();
+            assert_eq!((time_per_div.value, time_per_div.unit_name.as_str()), (5.0, "ms"));
+            assert_eq!((voltage_per_div.value, voltage_per_div.unit_name.as_str()), (2.0, "V"));
+        }
+    }
+
+    #[test]
+    fn ro_cut_off() {
+        let response = &ro_response(68)[..20];
+        assert!(matches!(check_ro_response(response), Err(OscilloscopeError::LengthMismatch{expected: RO_RESPONSE_LENGTH, received: 20})));
+    }
+
+    // a header of the given length, the samples and the CR
+    fn ri_frame(header: &[u8], header_length: usize, samples: usize) -> Vec<u8> {
+        let mut frame = header.to_vec();
+        frame.resize(header_length, b' ');
+        frame.extend(std::iter::repeat(0x80).take(samples));
+        frame.push(0x0D);
+        frame
+    }
+
+    #[test]
+    fn ri_frame_without_echo() {
+        // the fixed header is taken as it is, whatever the scope puts in it
+        let frame = ri_frame(b"", RI_HEADER_LENGTH, 1000);
+        assert_eq!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, None, RI_HEADER_LENGTH).unwrap(), RI_HEADER_LENGTH);
+        // also after a longer header was detected earlier
+        assert_eq!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, None, 18).unwrap(), RI_HEADER_LENGTH);
+        // a header length set by the user has to echo the command
+        assert!(matches!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, Some(RI_HEADER_LENGTH), RI_HEADER_LENGTH), Err(OscilloscopeError::HeaderMismatch)));
+        // a frame of any other length can't be placed without the echo
+        let frame = ri_frame(b"", 18, 1000);
+        assert!(matches!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, None, RI_HEADER_LENGTH), Err(OscilloscopeError::LengthMismatch{expected: 1015, received: 1019})));
+    }
+
+    #[test]
+    fn ri_frame_with_echo() {
+        let frame = ri_frame(b"R1(0000,1000,B)", 18, 1000);
+        assert_eq!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, None, RI_HEADER_LENGTH).unwrap(), 18);
+        assert_eq!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, Some(18), RI_HEADER_LENGTH).unwrap(), 18);
+        // the echo of another channel is not the frame that was asked for
+        assert!(matches!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY2, Some(18), RI_HEADER_LENGTH), Err(OscilloscopeError::HeaderMismatch)));
+        assert!(matches!(ri_frame_header_length(&frame, 1000, Channel::DISPLAY1, Some(RI_HEADER_LENGTH), RI_HEADER_LENGTH), Err(OscilloscopeError::LengthMismatch{expected: 1015, received: 1019})));
+    }
 }
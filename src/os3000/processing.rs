@@ -1,13 +1,15 @@
 use std::f64::consts::PI;
+use std::sync::LazyLock;
 
 use splines::{Key, Spline, Interpolation};
 use regex::Regex;
 use super::ValueUnitPair;
 
-pub fn parse_unit(parse_string: &str) -> Result<ValueUnitPair, ()> {
-    let match_pattern = Regex::new("(?<Value>[0-9]{1,3}|0.[0-9]{1,2})(?<Unit>mV|V|uV|s|ms|us)").unwrap();
+// get_scale_units may try every field of the condition string, so the pattern is only compiled once
+static UNIT_PATTERN: LazyLock<Regex> = LazyLock::new(|| Regex::new("(?<Value>[0-9]{1,3}|0.[0-9]{1,2})(?<Unit>mV|V|uV|s|ms|us)").unwrap());
 
-    if let Some(captures) = match_pattern.captures(parse_string) {
+pub fn parse_unit(parse_string: &str) -> Result<ValueUnitPair, ()> {
+    if let Some(captures) = UNIT_PATTERN.captures(parse_string) {
         if let Ok(value) = (&captures["Value"]).parse::<f64>() {
             let unit_name = &captures["Unit"];
             let unit_mult: f64 = match unit_name {
@@ -23,28 +25,61 @@ pub fn parse_unit(parse_string: &str) -> Result<ValueUnitPair, ()> {
 }
 
 
+fn is_time_unit(unit: &ValueUnitPair) -> bool {
+    unit.unit_name.ends_with('s')
+}
+
 pub fn get_scale_units(condition_string: &String) -> Result<(ValueUnitPair,ValueUnitPair), ()> {
-    let mut segments: Vec<&str> = condition_string.split(",").collect();
-    let time_unit: ValueUnitPair;
-    let voltage_unit: ValueUnitPair;
-    println!("{}", segments.len());
-    if segments.len() < 12 {
-        return  Err(());
+    // usually the time is the 4th and the voltage the 8th field, only if those don't fit they're searched for
+    // by their unit so extra, missing or reordered fields don't break parsing
+    // empty channels will not have time or voltage information, and neither will condition data that was
+    // incorrectly written to using the Wi command, both are an error instead of a panic
+    let segments: Vec<&str> = condition_string.split(",").collect();
+    let time_unit = segments.get(3).and_then(|segment| parse_unit(segment).ok()).filter(is_time_unit);
+    let voltage_unit = segments.get(7).and_then(|segment| parse_unit(segment).ok()).filter(|unit| !is_time_unit(unit));
+    if let (Some(time_unit), Some(voltage_unit)) = (time_unit, voltage_unit) {
+        return Ok((time_unit, voltage_unit));
     }
+    let units: Vec<ValueUnitPair> = segments.iter().filter_map(|segment| parse_unit(segment).ok()).collect();
+    let time_unit = units.iter().find(|unit| is_time_unit(unit)).ok_or(())?;
+    let voltage_unit = units.iter().find(|unit| !is_time_unit(unit)).ok_or(())?;
+    Ok((time_unit.clone(), voltage_unit.clone()))
+}
 
-    // empty channels will not have time or voltage information, but if one is present, we can assume the other is also present
-    // this may not be the case if somehow the condition data was incorrectly written to using the Wi command
-    
-    if let Ok(result) = parse_unit(segments[3]) {
-        time_unit = result;
-        voltage_unit = parse_unit(segments[7]).unwrap();
+pub fn is_empty_channel(condition_string: &String) -> bool {
+    // an empty channel still answers Ro with all fields, but without time or voltage information
+    let segments: Vec<&str> = condition_string.split(",").collect();
+    segments.len() >= 12 && segments.iter().all(|segment| parse_unit(segment).is_err())
+}
+
+pub fn format_si(value: f64, unit: &str) -> String {
+    // formats a value in base units with the closest SI prefix, e.g. 0.0023s becomes 2.300ms
+    let prefixes = [(1E-9, "n"), (1E-6, "u"), (1E-3, "m"), (1.0, ""), (1E3, "k"), (1E6, "M")];
+    let magnitude = value.abs();
+    let mut chosen = (1.0, "");
+    for (factor, prefix) in prefixes {
+        if magnitude >= factor {
+            chosen = (factor, prefix);
+        }
     }
-    else {
-        return Err(());
+    // tiny values get the smallest prefix instead of a long string of zeros
+    if magnitude != 0.0 && magnitude < prefixes[0].0 {
+        chosen = prefixes[0];
     }
-    
+    format!("{:.3}{}{}", value / chosen.0, chosen.1, unit)
+}
 
-    Ok((time_unit,voltage_unit))
+pub fn parse_si(text: &str, unit: &str) -> Result<f64, ()> {
+    // reads a value like format_si writes it, e.g. 1.5ms becomes 0.0015, the unit itself is optional
+    let text = text.trim();
+    let text = text.strip_suffix(unit).unwrap_or(text).trim_end();
+    let prefixes = [("n", 1E-9), ("u", 1E-6), ("µ", 1E-6), ("m", 1E-3), ("k", 1E3), ("M", 1E6)];
+    for (prefix, factor) in prefixes {
+        if let Some(number) = text.strip_suffix(prefix) {
+            return number.trim_end().parse::<f64>().map(|value| value * factor).map_err(|_| ());
+        }
+    }
+    text.parse::<f64>().map_err(|_| ())
 }
 
 pub fn scale_time(x:usize, time_per_divison:f64, scale_factor: f64) -> f64 {
@@ -53,12 +88,58 @@ pub fn scale_time(x:usize, time_per_divison:f64, scale_factor: f64) -> f64 {
     ((time_per_divison / 100.0) * x as f64) *scale_factor
 }
 
-pub fn scale_waveform_data(waveform_data_raw: &Vec<u8>, voltage_per_division: f64, scale_factor: f64) -> Vec<f64> {
+pub fn time_since(x: usize, zero: usize, time_per_division: f64) -> f64 {
+    // like scale_time, but t=0 is at sample zero, e.g. the trigger point, and earlier samples are negative
+    scale_time(x, time_per_division, 1.0) - scale_time(zero, time_per_division, 1.0)
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum SampleFormat {
+    // picks between offset binary and signed bytes by looking at the data
+    // only an opt-in, a square wave spanning more than half the range looks smoother read as signed
+    Auto,
+    // the OS3000 sends one unsigned byte per sample with the zero line at 128
+    #[default]
+    U8Offset128,
+    I8,
+    U16LE,
+    U16BE
+}
+
+pub fn detect_sample_format(waveform_data_raw: &[u8]) -> SampleFormat {
+    // decoded the wrong way, a signal crossing the zero line jumps across the whole range:
+    // offset binary read as signed wraps around at 128, signed read as offset binary wraps around at 0/255
+    // so whichever decoding gives the smoother trace is the right one
+    let roughness = |format: SampleFormat| -> f64 {
+        decode_samples(waveform_data_raw, format).windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum()
+    };
+    if roughness(SampleFormat::I8) < roughness(SampleFormat::U8Offset128) {
+        SampleFormat::I8
+    }
+    else {
+        SampleFormat::U8Offset128
+    }
+}
+
+pub fn decode_samples(waveform_data_raw: &[u8], format: SampleFormat) -> Vec<f64> {
+    // turns the raw bytes into signed sample values around the zero line, in units of 8 bit steps
+    match format {
+        SampleFormat::Auto => decode_samples(waveform_data_raw, detect_sample_format(waveform_data_raw)),
+        SampleFormat::U8Offset128 => waveform_data_raw.iter().map(|byte| *byte as f64 - 128.0).collect(),
+        SampleFormat::I8 => waveform_data_raw.iter().map(|byte| *byte as i8 as f64).collect(),
+        // 16 bit samples are scaled down so that a division is still 25 steps
+        SampleFormat::U16LE => waveform_data_raw.chunks_exact(2).map(|pair| (u16::from_le_bytes([pair[0], pair[1]]) as f64 - 32768.0) / 256.0).collect(),
+        SampleFormat::U16BE => waveform_data_raw.chunks_exact(2).map(|pair| (u16::from_be_bytes([pair[0], pair[1]]) as f64 - 32768.0) / 256.0).collect()
+    }
+}
+
+pub fn scale_waveform_data(waveform_data_raw: &Vec<u8>, voltage_per_division: f64, scale_factor: f64, format: SampleFormat) -> Vec<f64> {
     // This scales the raw sample data bytes to the correct voltage and time as f64s
-    let mut waveform_data_scaled: Vec<f64> = vec![0.0f64;1000];
-    for (i, sample_data) in waveform_data_raw.iter().enumerate() {
-        // since the zero line in the data is at 128, we need to subtract 128 to get to f64 0.0
-        let voltage_y_corrected = -((*sample_data as f64) - 128.0);
+    let samples = decode_samples(waveform_data_raw, format);
+    let mut waveform_data_scaled: Vec<f64> = vec![0.0f64;samples.len()];
+    for (i, sample_data) in samples.iter().enumerate() {
+        // the decoded samples are already centered around f64 0.0
+        let voltage_y_corrected = -sample_data;
         // since every y division has a size of 25, volts/div turns into volts/25
         let voltage_scaled = (voltage_y_corrected * (voltage_per_division / 25.0)) * scale_factor;
         // [time,voltage]
@@ -67,25 +148,141 @@ pub fn scale_waveform_data(waveform_data_raw: &Vec<u8>, voltage_per_division: f6
     waveform_data_scaled
 }
 
+pub fn count_clipped(raw: &[u8]) -> usize {
+    // samples at either end of the 8 bit range are clipped
+    raw.iter().filter(|sample| **sample == 0 || **sample == 255).count()
+}
+
+pub fn unscale_waveform_data(samples: &Vec<f64>, voltage_unit: &ValueUnitPair) -> Vec<u8> {
+    // turns scaled samples back into raw sample bytes, the inverse of scale_waveform_data and unit_scale
+    samples.iter().map(|sample| {
+        let voltage = sample / voltage_unit.unit_mult;
+        // every y division has a size of 25 and the zero line is at 128
+        let raw = 128.0 - (voltage * (25.0 / voltage_unit.value));
+        raw.round().clamp(0.0, 255.0) as u8
+    }).collect()
+}
+
+// trims the known gain and offset error of a probe or front end, the offset is in the unit of the samples
+#[derive(Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub gain: f64,
+    pub offset: f64
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration {gain: 1.0, offset: 0.0}
+    }
+}
+
+pub fn apply_calibration(samples: &mut [f64], calibration: &Calibration) {
+    // v * gain + offset, the samples are stored upside down so the offset is subtracted
+    for sample in samples.iter_mut() {
+        *sample = *sample * calibration.gain - calibration.offset;
+    }
+}
+
+pub fn despike(samples: &[f64], threshold_sigma: f64) -> Vec<f64> {
+    // replaces single wild points, e.g. from a corrupted byte, by interpolating between the good samples around them
+    // a spike sticks out from both of its neighbors in the same direction, an edge only from one, so edges are left alone
+    // the noise is estimated from the median difference between neighbors, a few spikes don't change that
+    let mut repaired = samples.to_vec();
+    if samples.len() < 3 {
+        return repaired;
+    }
+    let mut differences: Vec<f64> = samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect();
+    differences.sort_by(|a, b| a.total_cmp(b));
+    let mut sigma = differences[differences.len() / 2] * 1.4826;
+    if sigma == 0.0 {
+        // mostly flat signals, e.g. a clean square wave
+        sigma = differences.iter().sum::<f64>() / differences.len() as f64;
+    }
+    if sigma == 0.0 {
+        return repaired;
+    }
+    let limit = threshold_sigma * sigma;
+
+    // the first and the last sample only have one neighbor, a spike there can't be told apart from an edge
+    let bad: Vec<bool> = (0..samples.len()).map(|i| {
+        if i == 0 || i == samples.len() - 1 {
+            return false;
+        }
+        let (to_previous, to_next) = (samples[i] - samples[i - 1], samples[i] - samples[i + 1]);
+        to_previous.signum() == to_next.signum() && to_previous.abs().min(to_next.abs()) > limit
+    }).collect();
+
+    let mut i = 0;
+    while i < samples.len() {
+        if !bad[i] {
+            i += 1;
+            continue;
+        }
+        // a run of bad samples is bridged by a straight line between the good ones around it
+        let start = i - 1;
+        let mut end = i;
+        while bad[end] {
+            end += 1;
+        }
+        for (j, sample) in repaired.iter_mut().enumerate().take(end).skip(start + 1) {
+            *sample = samples[start] + (samples[end] - samples[start]) * (j - start) as f64 / (end - start) as f64;
+        }
+        i = end;
+    }
+    repaired
+}
+
 pub fn unit_scale(samples: &Vec<f64>, voltage_unit: &ValueUnitPair) -> Vec<f64> {
     let scaled_samples = samples.iter().map(|sample| {sample * voltage_unit.unit_mult}).collect();
     scaled_samples
 }  
 
 
+// number of reconstructed points dropped at either end of the trace
+// the splines need neighbouring keys that don't exist at the edges, linear and cosine don't
+pub const LINEAR_EDGE_SKIP: usize = 0;
+pub const COSINE_EDGE_SKIP: usize = 0;
+pub const SPLINE_EDGE_SKIP: usize = 2;
+
+fn output_time(i: usize, num_samples: usize, sample_count: usize, time_per_divison: f64) -> f64 {
+    // point i of num_samples sits at sample position i * sample_count / num_samples, where raw_index_to_x draws it,
+    // so the whole capture is drawn whatever the record length
+    scale_time(i, time_per_divison, 1.0) * sample_count as f64 / num_samples as f64
+}
+
+pub fn auto_first_pass_samples(sample_count: usize) -> usize {
+    // the samples as they are, a deep record is averaged down to twice a single record so it doesn't slow down the drawing
+    sample_count.min(2 * super::DEFAULT_RECORD_LENGTH as usize)
+}
+
+pub fn fit_sample_count(samples: &[f64], num_samples: usize, time_per_divison: f64, step: usize) -> Vec<f64> {
+    // the first interpolation pass: averages down to fewer points, interpolates linearly to more
+    // and leaves the samples alone if there are already as many, so they aren't interpolated twice
+    if num_samples < samples.len() {
+        decimate_average(samples, num_samples)
+    }
+    else if num_samples == samples.len() && step == 1 {
+        samples.to_vec()
+    }
+    else {
+        linear_interpolate_samples(&samples.to_vec(), num_samples, time_per_divison, step)
+    }
+}
+
 pub fn cosine_interpolate_samples(samples: &Vec<f64>, num_samples: usize, time_per_divison:f64, step: usize) -> Vec<f64> {
     // Interpolates samples to n samples using Linear interpolation
     let mut keys: Vec<Key<f64,f64>> = Vec::with_capacity(samples.len() / step);
     let mut new_values:Vec<f64> = Vec::with_capacity(num_samples);
     
+    // keyed at the sample's own time like the linear one, shifted by a sample the last one would never be reached
     for i in (0..samples.len()).step_by(step) {
-        keys.push(Key::new(scale_time(i + 1, time_per_divison, 1.0),samples[i], Interpolation::Cosine));
+        keys.push(Key::new(scale_time(i, time_per_divison, 1.0),samples[i], Interpolation::Cosine));
     }
 
     // create a spline from the keys we got from the samples
     let spline = Spline::from_vec(keys);
-    for i in 4..num_samples-4 {
-        let x = scale_time(i, time_per_divison, 1.0)/(num_samples as f64 / 1000.0);
+    for i in COSINE_EDGE_SKIP..num_samples-COSINE_EDGE_SKIP {
+        let x = output_time(i, num_samples, samples.len(), time_per_divison);
         if let Some(y_interpolated) = spline.clamped_sample(x) {
             new_values.push(y_interpolated);
         }
@@ -117,8 +314,8 @@ pub fn catmull_rom_interpolate_samples(samples: &Vec<f64>, num_samples: usize, t
 
     // create a spline from the keys we got from the samples
     let spline = Spline::from_vec(keys);
-    for i in 4..num_samples-4 {
-        let x = scale_time(i, time_per_divison, 1.0)/(num_samples as f64 / 1000.0);
+    for i in SPLINE_EDGE_SKIP..num_samples-SPLINE_EDGE_SKIP {
+        let x = output_time(i, num_samples, samples.len(), time_per_divison);
         if let Some(y_interpolated) = spline.clamped_sample(x) {
             new_values.push(y_interpolated);
         }
@@ -150,8 +347,8 @@ pub fn bezier2_interpolate_samples(samples: &Vec<f64>, num_samples: usize, time_
 
     // create a spline from the keys we got from the samples
     let spline = Spline::from_vec(keys);
-    for i in 4..num_samples-4 {
-        let x = scale_time(i, time_per_divison, 1.0)/(num_samples as f64 / 1000.0);
+    for i in SPLINE_EDGE_SKIP..num_samples-SPLINE_EDGE_SKIP {
+        let x = output_time(i, num_samples, samples.len(), time_per_divison);
         if let Some(y_interpolated) = spline.clamped_sample(x) {
             new_values.push(y_interpolated);
         }
@@ -182,8 +379,8 @@ pub fn bezier_interpolate_samples(samples: &Vec<f64>, num_samples: usize, time_p
 
     // create a spline from the keys we got from the samples
     let spline = Spline::from_vec(keys);
-    for i in 4..num_samples-4 {
-        let x = scale_time(i, time_per_divison, 1.0)/(num_samples as f64 / 1000.0);
+    for i in SPLINE_EDGE_SKIP..num_samples-SPLINE_EDGE_SKIP {
+        let x = output_time(i, num_samples, samples.len(), time_per_divison);
         if let Some(y_interpolated) = spline.clamped_sample(x) {
             new_values.push(y_interpolated);
         }
@@ -215,8 +412,8 @@ pub fn linear_interpolate_samples(samples: &Vec<f64>, num_samples: usize, time_p
 
     // create a spline from the keys we got from the samples
     let spline = Spline::from_vec(keys);
-    for i in 4..num_samples-4 {
-        let x = scale_time(i, time_per_divison, 1.0)/(num_samples as f64 / 1000.0);
+    for i in LINEAR_EDGE_SKIP..num_samples-LINEAR_EDGE_SKIP {
+        let x = output_time(i, num_samples, samples.len(), time_per_divison);
         if let Some(y_interpolated) = spline.clamped_sample(x) {
             new_values.push(y_interpolated);
         }
@@ -234,6 +431,92 @@ pub fn linear_interpolate_samples(samples: &Vec<f64>, num_samples: usize, time_p
 
 }
 
+pub fn node_interpolate_samples(samples: &[f64], num_samples: usize) -> Vec<f64> {
+    // Catmull-Rom between the samples, point j sits at sample position j * samples.len() / num_samples like the raw samples are drawn
+    // wherever that position is a whole sample the point is that sample, so with num_samples a multiple of the sample count
+    // every captured value is exactly on the trace and only the points between them are interpolated
+    let n = samples.len();
+    if n == 0 {
+        return vec![0.0; num_samples];
+    }
+    let at = |i: isize| samples[i.clamp(0, n as isize - 1) as usize];
+    (0..num_samples).map(|j| {
+        let position = j * n;
+        let (i, remainder) = (position / num_samples, position % num_samples);
+        if remainder == 0 {
+            return samples[i];
+        }
+        let t = remainder as f64 / num_samples as f64;
+        let i = i as isize;
+        let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+        0.5 * (2.0 * p1 + (p2 - p0) * t + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+    }).collect()
+}
+
+pub fn node_aligned_samples(num_samples: usize, sample_count: usize) -> usize {
+    // the closest multiple of the sample count, at least one sample count
+    if sample_count == 0 {
+        return num_samples;
+    }
+    ((num_samples as f64 / sample_count as f64).round() as usize).max(1) * sample_count
+}
+
+pub fn interp_residual(spline: &[f64], linear: &[f64]) -> Vec<f64> {
+    // how far an interpolation strays from straight lines between the samples, overshoot and ringing show up as bumps
+    spline.iter().zip(linear).map(|(spline, linear)| spline - linear).collect()
+}
+
+pub fn diff(a: &[f64], b: &[f64]) -> Vec<f64> {
+    // a - b for every sample, b is stretched onto the length of a if they don't match
+    if b.len() < 2 {
+        return a.to_vec();
+    }
+    let b_resampled: Vec<f64>;
+    let b = if a.len() == b.len() {b} else {
+        b_resampled = interpolate_range(b, 0.0, (b.len() - 1) as f64, a.len());
+        &b_resampled
+    };
+    a.iter().zip(b.iter()).map(|(a, b)| a - b).collect()
+}
+
+pub fn value_at(samples: &[f64], position: f64) -> f64 {
+    // linear interpolation at a fractional sample position, clamped to the ends
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let position = position.clamp(0.0, (samples.len() - 1) as f64);
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    samples[lower] + (samples[upper] - samples[lower]) * fraction
+}
+
+pub fn resample_time_base(samples: &[f64], seconds_per_div: f64, target_len: usize, target_seconds_per_div: f64) -> (Vec<f64>, usize) {
+    // maps samples taken at one time base onto the sample times of another, both start at t=0
+    // returns the resampled samples and how many of them are actually covered by the original samples,
+    // everything after that is the last sample repeated
+    if samples.is_empty() || seconds_per_div <= 0.0 || target_seconds_per_div <= 0.0 {
+        return (samples.to_vec(), samples.len());
+    }
+    let ratio = target_seconds_per_div / seconds_per_div;
+    let last_index = (samples.len() - 1) as f64;
+    let resampled: Vec<f64> = (0..target_len).map(|i| value_at(samples, i as f64 * ratio)).collect();
+    let covered = (0..target_len).take_while(|i| *i as f64 * ratio <= last_index).count();
+    (resampled, covered)
+}
+
+pub fn interpolate_range(samples: &[f64], start: f64, end: f64, num_samples: usize) -> Vec<f64> {
+    // linearly interpolates num_samples points between the (fractional) sample positions start and end
+    let mut new_values: Vec<f64> = Vec::with_capacity(num_samples);
+    if samples.is_empty() || num_samples < 2 {
+        return new_values;
+    }
+    for i in 0..num_samples {
+        new_values.push(value_at(samples, start + (end - start) * i as f64 / (num_samples - 1) as f64));
+    }
+    new_values
+}
+
 pub fn moving_average_filter(samples: &Vec<f64>, window_size: usize) -> Vec<f64> {
     let mut filtered_samples: Vec<f64> = Vec::with_capacity(samples.len());
     // calculate the initial sum
@@ -269,6 +552,906 @@ pub fn moving_average_filter(samples: &Vec<f64>, window_size: usize) -> Vec<f64>
     filtered_samples
 }
 
+fn solve_linear_system(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    // gauss-jordan elimination with partial pivoting, solves matrix * x = rhs for every column of rhs
+    let n = matrix.len();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|a, b| matrix[*a][column].abs().total_cmp(&matrix[*b][column].abs()))?;
+        if matrix[pivot][column].abs() < 1E-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+
+        let divisor = matrix[column][column];
+        matrix[column].iter_mut().for_each(|value| *value /= divisor);
+        rhs[column].iter_mut().for_each(|value| *value /= divisor);
+        let (pivot_row, pivot_rhs) = (matrix[column].clone(), rhs[column].clone());
+        for (row, (matrix_row, rhs_row)) in matrix.iter_mut().zip(rhs.iter_mut()).enumerate() {
+            if row == column {
+                continue;
+            }
+            let factor = matrix_row[column];
+            matrix_row.iter_mut().zip(&pivot_row).for_each(|(value, pivot)| *value -= factor * pivot);
+            rhs_row.iter_mut().zip(&pivot_rhs).for_each(|(value, pivot)| *value -= factor * pivot);
+        }
+    }
+    Some(rhs)
+}
+
+pub fn savgol_filter(samples: &[f64], window: usize, poly_order: usize) -> Vec<f64> {
+    // fits a polynomial of poly_order to the window around every sample and takes its value at that sample
+    // near the edges the first or last full window is used, evaluated off-center, so nothing gets cut off
+    let window = if window.is_multiple_of(2) {window + 1} else {window};
+    if window <= poly_order || samples.len() < window {
+        return samples.to_vec();
+    }
+    let half = (window / 2) as isize;
+    let terms = poly_order + 1;
+
+    // least squares: (A^T A) x = A^T, with A[j][k] = t_j^k for the positions t_j = -half..=half
+    let positions: Vec<f64> = (-half..=half).map(|t| t as f64).collect();
+    let normal: Vec<Vec<f64>> = (0..terms).map(|row| {
+        (0..terms).map(|column| positions.iter().map(|t| t.powi((row + column) as i32)).sum()).collect()
+    }).collect();
+    let design_t: Vec<Vec<f64>> = (0..terms).map(|row| positions.iter().map(|t| t.powi(row as i32)).collect()).collect();
+    let Some(fit) = solve_linear_system(normal, design_t) else {
+        return samples.to_vec();
+    };
+    // convolution weights for evaluating the fit at offset t from the window center
+    let weights = |t: f64| -> Vec<f64> {
+        (0..window).map(|j| (0..terms).map(|k| t.powi(k as i32) * fit[k][j]).sum()).collect()
+    };
+    let center_weights = weights(0.0);
+
+    let len = samples.len() as isize;
+    (0..len).map(|i| {
+        let start = (i - half).clamp(0, len - window as isize);
+        let offset = (i - start - half) as f64;
+        let window_samples = &samples[start as usize..start as usize + window];
+        if offset == 0.0 {
+            window_samples.iter().zip(center_weights.iter()).map(|(sample, weight)| sample * weight).sum()
+        } else {
+            window_samples.iter().zip(weights(offset).iter()).map(|(sample, weight)| sample * weight).sum()
+        }
+    }).collect()
+}
+
+pub fn notch_filter(samples: &[f64], notch_hz: f64, sample_rate: f64, q: f64) -> Vec<f64> {
+    // biquad notch from the audio eq cookbook, a higher q makes the notch narrower
+    // frequencies at or above nyquist can't be in the capture, so there's nothing to remove
+    if samples.is_empty() || notch_hz <= 0.0 || q <= 0.0 || notch_hz >= sample_rate / 2.0 {
+        return samples.to_vec();
+    }
+    let omega = 2.0 * PI * notch_hz / sample_rate;
+    let alpha = omega.sin() / (2.0 * q);
+    let a0 = 1.0 + alpha;
+    let (b0, b1, b2) = (1.0 / a0, -2.0 * omega.cos() / a0, 1.0 / a0);
+    let (a1, a2) = (-2.0 * omega.cos() / a0, (1.0 - alpha) / a0);
+
+    // the notch passes dc unchanged, starting from the first sample avoids a jump at the left edge
+    let (mut x1, mut x2, mut y1, mut y2) = (samples[0], samples[0], samples[0], samples[0]);
+    samples.iter().map(|x| {
+        let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        (x2, x1, y2, y1) = (x1, *x, y1, y);
+        y
+    }).collect()
+}
+
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    // in place radix-2 FFT, the length has to be a power of two
+    let n = re.len();
+    // put the samples into bit reversed order
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let (t_re, t_im) = (re[b] * w_re - im[b] * w_im, re[b] * w_im + im[b] * w_re);
+                (re[b], im[b]) = (re[a] - t_re, im[a] - t_im);
+                (re[a], im[a]) = (re[a] + t_re, im[a] + t_im);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// name of the window compute_spectrum uses, for the header of exported spectra
+pub const SPECTRUM_WINDOW: &str = "hann";
+
+fn windowed_fft(samples: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    // the mean is removed and a hann window keeps a non-integer number of periods from smearing over all bins
+    let n = samples.len().next_power_of_two();
+    let level = mean(samples);
+    let mut re = vec![0.0; n];
+    let mut im = vec![0.0; n];
+    for (i, sample) in samples.iter().enumerate() {
+        let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / (samples.len() - 1) as f64).cos();
+        re[i] = (sample - level) * window;
+    }
+    fft(&mut re, &mut im);
+    (re, im)
+}
+
+pub fn compute_spectrum(samples: &[f64], sample_rate: f64) -> (Vec<f64>, f64) {
+    // amplitude of every frequency bin from 0 up to half the sample rate, and the width of one bin in Hz
+    if samples.len() < 2 || sample_rate <= 0.0 {
+        return (Vec::new(), 0.0);
+    }
+    let (re, im) = windowed_fft(samples);
+    // the window halves the amplitude and the other half of it is in the negative frequencies
+    let scale = 4.0 / samples.len() as f64;
+    let spectrum = (0..=re.len() / 2).map(|i| re[i].hypot(im[i]) * scale).collect();
+    (spectrum, sample_rate / re.len() as f64)
+}
+
+pub fn compute_spectrum_with_phase(samples: &[f64], sample_rate: f64) -> (Vec<(f64, f64)>, Vec<f64>) {
+    // (frequency, amplitude) of every bin like compute_spectrum, and the phase of every bin in degrees
+    if samples.len() < 2 || sample_rate <= 0.0 {
+        return (Vec::new(), Vec::new());
+    }
+    let (re, im) = windowed_fft(samples);
+    let scale = 4.0 / samples.len() as f64;
+    let bin_width = sample_rate / re.len() as f64;
+    let spectrum = (0..=re.len() / 2).map(|i| (i as f64 * bin_width, re[i].hypot(im[i]) * scale)).collect();
+    let phases = (0..=re.len() / 2).map(|i| im[i].atan2(re[i]).to_degrees()).collect();
+    (spectrum, phases)
+}
+
+#[derive(Clone, Default)]
+pub struct RunningStats {
+    count: usize,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64
+}
+
+impl RunningStats {
+    pub fn push(&mut self, value: f64) {
+        // Welford's online algorithm, so we never have to keep all the values around
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.m2 / (self.count - 1) as f64
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn reset(&mut self) {
+        *self = RunningStats::default();
+    }
+}
+
+pub fn peak_to_peak(samples: &[f64]) -> f64 {
+    let (min, max) = samples.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)));
+    if samples.is_empty() {0.0} else {max - min}
+}
+
+pub fn extremes(samples: &[f64]) -> Option<(usize, usize)> {
+    // indices of the smallest and the largest sample, the first one wins on ties
+    if samples.is_empty() {
+        return None;
+    }
+    let (mut min_index, mut max_index) = (0, 0);
+    for (i, sample) in samples.iter().enumerate() {
+        if *sample < samples[min_index] {min_index = i;}
+        if *sample > samples[max_index] {max_index = i;}
+    }
+    Some((min_index, max_index))
+}
+
+pub fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+pub fn dc_average(samples: &[f64], end: usize, window: usize) -> Option<f64> {
+    // mean over the last `window` samples before `end`, shorter if the window reaches past the start
+    let end = end.min(samples.len());
+    if end == 0 || window == 0 {
+        return None;
+    }
+    Some(mean(&samples[end.saturating_sub(window)..end]))
+}
+
+pub fn integral_between(samples: &[f64], start: usize, end: usize, dt: f64) -> f64 {
+    // trapezoidal integral from sample start to sample end, in the sample unit times seconds
+    // the cursors can be in either order, swapping them flips the sign like a reversed integral would
+    let (low, high) = (start.min(end), start.max(end).min(samples.len().saturating_sub(1)));
+    if low >= high {
+        return 0.0;
+    }
+    let area: f64 = samples[low..=high].windows(2).map(|pair| (pair[0] + pair[1]) / 2.0 * dt).sum();
+    if start <= end {area} else {-area}
+}
+
+pub fn rms(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|sample| sample * sample).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+pub fn cycle_rms(samples: &[f64], sample_rate: f64) -> f64 {
+    // rms over a whole number of cycles, from the first to the last rising crossing of the mean
+    // the partial cycles at both ends bias the plain rms, without a frequency the whole capture is used
+    if frequency(samples, sample_rate).is_none() {
+        return rms(samples);
+    }
+    let level = mean(samples);
+    let rising: Vec<usize> = find_crossings(samples, level).into_iter().filter(|i| samples[*i] >= level).collect();
+    rms(&samples[rising[0]..rising[rising.len() - 1]])
+}
+
+// window of the light smoothing that can go before the slew rate, a single noisy sample would be the steepest step otherwise
+pub const SLEW_SMOOTHING_WINDOW: usize = 5;
+
+pub fn steepest_step(samples: &[f64]) -> Option<usize> {
+    // the first sample of the largest step between two neighboring samples, in either direction
+    (0..samples.len().saturating_sub(1)).max_by(|a, b| (samples[*a + 1] - samples[*a]).abs().total_cmp(&(samples[*b + 1] - samples[*b]).abs()))
+}
+
+pub fn max_slew_rate(samples: &[f64], dt: f64) -> f64 {
+    // the steepest step per second, dt is the time between two samples
+    match steepest_step(samples) {
+        Some(i) if dt > 0.0 => (samples[i + 1] - samples[i]).abs() / dt,
+        _ => 0.0
+    }
+}
+
+pub fn crest_factor(samples: &[f64]) -> f64 {
+    // peak / rms, sqrt(2) for a sine, 1 for a square wave
+    let rms = rms(samples);
+    if rms == 0.0 {
+        return 0.0;
+    }
+    samples.iter().fold(0.0f64, |peak, sample| peak.max(sample.abs())) / rms
+}
+
+pub fn form_factor(samples: &[f64]) -> f64 {
+    // rms / rectified mean, about 1.11 for a sine
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let rectified_mean = samples.iter().map(|sample| sample.abs()).sum::<f64>() / samples.len() as f64;
+    if rectified_mean == 0.0 {
+        return 0.0;
+    }
+    rms(samples) / rectified_mean
+}
+
+pub fn power_watts(vrms: f64, impedance_ohms: f64) -> f64 {
+    // P = Vrms^2 / R
+    if impedance_ohms <= 0.0 {
+        return 0.0;
+    }
+    vrms * vrms / impedance_ohms
+}
+
+pub fn power_dbm(vrms: f64, impedance_ohms: f64) -> f64 {
+    // dBm is relative to 1mW, zero power ends up as negative infinity
+    10.0 * (power_watts(vrms, impedance_ohms) * 1000.0).log10()
+}
+
+pub fn decimate_average(samples: &[f64], num_samples: usize) -> Vec<f64> {
+    // reduces the samples to num_samples by averaging evenly sized blocks, so nothing is simply skipped
+    if num_samples == 0 || num_samples >= samples.len() {
+        return samples.to_vec();
+    }
+    (0..num_samples).map(|i| {
+        let (start, end) = (i * samples.len() / num_samples, (i + 1) * samples.len() / num_samples);
+        mean(&samples[start..end.max(start + 1)])
+    }).collect()
+}
+
+pub fn accumulate_average(avg: &mut Vec<f64>, new: &[f64], n: usize) {
+    // exponential average over roughly the last n captures
+    // start over if the length changed, there's nothing sensible to average then
+    if avg.len() != new.len() || n <= 1 {
+        avg.clear();
+        avg.extend_from_slice(new);
+        return;
+    }
+    for (average, sample) in avg.iter_mut().zip(new) {
+        *average += (sample - *average) / n as f64;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TriggerSlope {
+    Rising,
+    Falling
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum TriggerCoupling {
+    // the signal as it was captured
+    #[default]
+    Dc,
+    // the signal without its mean, like behind a coupling capacitor
+    Ac,
+    // like DC, but the signal has to leave a band around the level before it can trigger again
+    NoiseReject
+}
+
+// half the width of the noise reject band, as a fraction of the peak to peak range
+pub const NOISE_REJECT_HYSTERESIS: f64 = 0.1;
+
+#[derive(Clone, Copy)]
+pub struct TriggerConfig {
+    pub level: f64,
+    pub slope: TriggerSlope,
+    pub coupling: TriggerCoupling,
+    // minimum number of samples between two accepted triggers
+    pub holdoff_samples: usize
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        TriggerConfig{level: 0.0, slope: TriggerSlope::Rising, coupling: TriggerCoupling::Dc, holdoff_samples: 0}
+    }
+}
+
+pub fn find_trigger_index(samples: &[f64], trigger: &TriggerConfig) -> Option<usize> {
+    // returns the first sample at which the signal crosses the trigger level with the right slope
+    find_trigger_indexes(samples, trigger).first().copied()
+}
+
+pub fn find_trigger_indexes(samples: &[f64], trigger: &TriggerConfig) -> Vec<usize> {
+    // returns every sample at which the signal crosses the trigger level with the right slope
+    // with AC coupling the level counts from the mean of the signal instead of from zero
+    let level = if trigger.coupling == TriggerCoupling::Ac {trigger.level + mean(samples)} else {trigger.level};
+    let band = if trigger.coupling == TriggerCoupling::NoiseReject {peak_to_peak(samples) * NOISE_REJECT_HYSTERESIS} else {0.0};
+    let mut triggers: Vec<usize> = Vec::new();
+    // armed once the signal has been below (rising) or above (falling) the level, minus the band
+    let mut armed = false;
+    // during the holdoff after a trigger the signal is ignored completely, it can't even arm the trigger
+    let mut holdoff_end = 0;
+    for (i, sample) in samples.iter().enumerate() {
+        if i < holdoff_end {
+            continue;
+        }
+        let triggered = match trigger.slope {
+            TriggerSlope::Rising => {
+                let triggered = armed && *sample >= level;
+                armed |= *sample < level - band;
+                triggered
+            },
+            TriggerSlope::Falling => {
+                let triggered = armed && *sample <= level;
+                armed |= *sample > level + band;
+                triggered
+            }
+        };
+        if triggered {
+            triggers.push(i);
+            armed = false;
+            holdoff_end = i + trigger.holdoff_samples.max(1);
+        }
+    }
+    triggers
+}
+
+pub fn find_crossings(samples: &[f64], level: f64) -> Vec<usize> {
+    // returns the indexes at which the signal crosses the level, in either direction
+    let mut crossings: Vec<usize> = Vec::new();
+    for i in 1..samples.len() {
+        let (last, current) = (samples[i - 1], samples[i]);
+        if (last < level && current >= level) || (last >= level && current < level) {
+            crossings.push(i);
+        }
+    }
+    crossings
+}
+
+pub fn estimate_unit_interval(samples: &[f64], level: f64) -> Option<usize> {
+    // the shortest distance between two crossings is (roughly) one unit interval of a digital signal
+    let crossings = find_crossings(samples, level);
+    crossings.windows(2).map(|pair| pair[1] - pair[0]).filter(|gap| *gap > 1).min()
+}
+
+pub fn sample_rate(time_per_div: &ValueUnitPair) -> f64 {
+    // every division has 100 samples, see scale_time
+    if time_per_div.unit_mult == 0.0 || time_per_div.value == 0.0 {
+        return 0.0;
+    }
+    100.0 / (time_per_div.value / time_per_div.unit_mult)
+}
+
+pub fn frequency(samples: &[f64], sample_rate: f64) -> Option<f64> {
+    // count full periods between the first and the last rising crossing of the mean
+    let level = mean(samples);
+    let rising: Vec<usize> = find_crossings(samples, level).into_iter().filter(|i| samples[*i] >= level).collect();
+    if rising.len() < 2 || sample_rate <= 0.0 {
+        return None;
+    }
+    let periods = (rising.len() - 1) as f64;
+    let span = (rising[rising.len() - 1] - rising[0]) as f64 / sample_rate;
+    let frequency = periods / span;
+    // with less than one and a half cycles in the capture the crossings could be noise
+    if samples.len() as f64 / sample_rate * frequency < MIN_MEASUREMENT_CYCLES {
+        return None;
+    }
+    Some(frequency)
+}
+
+pub fn period_jitter(samples: &[f64], time_per_div: f64) -> Option<(f64, f64)> {
+    // mean and standard deviation of the periods between successive rising crossings of the mean
+    // the crossings are interpolated between the two samples around them, whole samples would be the jitter otherwise
+    let level = mean(samples);
+    let crossings: Vec<f64> = find_crossings(samples, level).into_iter()
+        .filter(|i| samples[*i] >= level)
+        .map(|i| (i - 1) as f64 + (level - samples[i - 1]) / (samples[i] - samples[i - 1]))
+        .collect();
+    // a spread needs at least two periods
+    if crossings.len() < 3 || time_per_div <= 0.0 {
+        return None;
+    }
+    let sample_time = scale_time(1, time_per_div, 1.0);
+    let periods: Vec<f64> = crossings.windows(2).map(|pair| (pair[1] - pair[0]) * sample_time).collect();
+    let mean_period = mean(&periods);
+    let variance = periods.iter().map(|period| (period - mean_period).powi(2)).sum::<f64>() / (periods.len() - 1) as f64;
+    Some((mean_period, variance.sqrt()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ReferenceLevels {
+    // low and high level of a two level signal, for when the extremes of a noisy or ringing signal are the wrong ones
+    pub low: f64,
+    pub high: f64
+}
+
+fn signal_levels(samples: &[f64], levels: Option<ReferenceLevels>) -> Option<(f64, f64)> {
+    // the levels set by hand if there are any, the extremes of the signal otherwise
+    let (min, max) = match levels {
+        Some(levels) => (levels.low, levels.high),
+        None => samples.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)))
+    };
+    if max <= min {None} else {Some((min, max))}
+}
+
+pub fn edge_time(samples: &[f64], sample_rate: f64, rising: bool, levels: Option<ReferenceLevels>) -> Option<f64> {
+    // time of the first edge from 10% to 90% of the peak to peak range (or 90% to 10% when falling)
+    let (min, max) = signal_levels(samples, levels)?;
+    if sample_rate <= 0.0 {
+        return None;
+    }
+    let (low, high) = (min + 0.1 * (max - min), min + 0.9 * (max - min));
+    let (start_level, end_level) = if rising {(low, high)} else {(high, low)};
+    let passed = |sample: f64, level: f64| if rising {sample >= level} else {sample <= level};
+
+    let mut start: Option<usize> = None;
+    for i in 1..samples.len() {
+        match start {
+            None => if !passed(samples[i - 1], start_level) && passed(samples[i], start_level) {
+                start = Some(i);
+            },
+            Some(first) => {
+                if passed(samples[i], end_level) {
+                    return Some((i - first) as f64 / sample_rate);
+                }
+                // the signal went back before finishing the edge, wait for the next one
+                if !passed(samples[i], start_level) {
+                    start = None;
+                }
+            }
+        }
+    }
+    None
+}
+
+pub fn settling_time(samples: &[f64], time_per_div: f64, tolerance_frac: f64) -> Option<f64> {
+    // time from the start of the step until the signal stays within the tolerance band around its final level
+    // the final level is the average of the last tenth of the samples, the band is a fraction of the step size
+    if samples.len() < 10 {
+        return None;
+    }
+    let final_level = mean(&samples[samples.len() - samples.len() / 10..]);
+    let initial_level = samples[0];
+    let band = (final_level - initial_level).abs() * tolerance_frac;
+    if band <= 0.0 {
+        return None;
+    }
+    // the step starts when the signal leaves the initial level
+    let start = samples.iter().position(|sample| (sample - initial_level).abs() > band)?;
+    // and is settled after the last sample outside of the final band
+    let last_outside = samples.iter().rposition(|sample| (sample - final_level).abs() > band)?;
+    if last_outside + 1 >= samples.len() || last_outside < start {
+        return None;
+    }
+    Some(scale_time(last_outside + 1 - start, time_per_div, 1.0))
+}
+
+pub fn duty_cycle(samples: &[f64], levels: Option<ReferenceLevels>) -> Option<f64> {
+    // fraction of the samples above the middle of the peak to peak range
+    let (min, max) = signal_levels(samples, levels)?;
+    let level = (min + max) / 2.0;
+    // needs at least one full cycle, i.e. three crossings
+    if find_crossings(samples, level).len() < 3 {
+        return None;
+    }
+    Some(samples.iter().filter(|sample| **sample > level).count() as f64 / samples.len() as f64)
+}
+
+// half the width of the band around the mid level that pulse edges have to cross, as a fraction of the peak to peak range
+pub const PULSE_HYSTERESIS: f64 = 0.1;
+
+#[derive(Clone, Copy)]
+pub struct MarkSpace {
+    // mean time high and low, in seconds
+    pub mark: f64,
+    pub space: f64,
+    pub ratio: f64,
+    // how far the high time is off from half of a period, 0.0 for a perfectly symmetric signal
+    pub asymmetry: f64
+}
+
+pub fn mark_space(samples: &[f64], time_per_div: f64, levels: Option<ReferenceLevels>) -> Option<MarkSpace> {
+    // only complete high and low periods count, the capture cuts the first and last one off
+    let (min, max) = signal_levels(samples, levels)?;
+    if time_per_div <= 0.0 {
+        return None;
+    }
+    let level = (min + max) / 2.0;
+    let band = (max - min) * PULSE_HYSTERESIS;
+
+    // the signal only changes state once it is through the band, so noise on an edge doesn't add short pulses
+    let mut high: Option<bool> = None;
+    let mut last_edge: Option<usize> = None;
+    let (mut highs, mut lows): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+    for (i, sample) in samples.iter().enumerate() {
+        let state = if *sample > level + band {true} else if *sample < level - band {false} else {continue};
+        if high == Some(!state) {
+            if let Some(edge) = last_edge {
+                if state {lows.push(i - edge)} else {highs.push(i - edge)}
+            }
+            last_edge = Some(i);
+        }
+        high = Some(state);
+    }
+    if highs.is_empty() || lows.is_empty() {
+        return None;
+    }
+    let sample_time = scale_time(1, time_per_div, 1.0);
+    let mark = highs.iter().sum::<usize>() as f64 / highs.len() as f64 * sample_time;
+    let space = lows.iter().sum::<usize>() as f64 / lows.len() as f64 * sample_time;
+    Some(MarkSpace {mark, space, ratio: mark / space, asymmetry: (mark - space) / (mark + space)})
+}
+
+pub fn count_pulses(samples: &[f64], level: f64, rising: bool) -> usize {
+    // counts the edges in one direction through the level, with the same band around it as mark_space
+    // the state the capture starts in doesn't count as an edge
+    let band = peak_to_peak(samples) * PULSE_HYSTERESIS;
+    let mut high: Option<bool> = None;
+    let mut count = 0;
+    for sample in samples {
+        let state = if *sample > level + band {true} else if *sample < level - band {false} else {continue};
+        if high == Some(!state) && state == rising {
+            count += 1;
+        }
+        high = Some(state);
+    }
+    count
+}
+
+// measurements that need a periodic signal want at least this many cycles in the capture
+pub const MIN_MEASUREMENT_CYCLES: f64 = 1.5;
+// fewer samples than this can't be measured sensibly at all
+pub const MIN_MEASUREMENT_SAMPLES: usize = 10;
+
+#[derive(Clone, Default)]
+pub struct MeasurementSet {
+    // every measurement is None when it can't be trusted for this capture
+    pub vpp: Option<f64>,
+    pub vrms: Option<f64>,
+    // rms over whole cycles, see cycle_rms
+    pub cycle_rms: Option<f64>,
+    pub mean: Option<f64>,
+    pub frequency: Option<f64>,
+    pub period: Option<f64>,
+    pub period_jitter: Option<f64>,
+    pub rise_time: Option<f64>,
+    pub fall_time: Option<f64>,
+    pub duty_cycle: Option<f64>,
+    pub mark_space: Option<MarkSpace>,
+    pub crest_factor: Option<f64>,
+    pub form_factor: Option<f64>,
+    pub unit: String
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Measurement {
+    Vpp,
+    Vrms,
+    CycleRms,
+    Mean,
+    Frequency,
+    Period,
+    PeriodJitter,
+    RiseTime,
+    FallTime,
+    DutyCycle,
+    Mark,
+    Space,
+    CrestFactor,
+    FormFactor
+}
+
+impl Measurement {
+    pub const ALL: [Measurement; 14] = [
+        Self::Vpp, Self::Vrms, Self::CycleRms, Self::Mean, Self::Frequency, Self::Period, Self::PeriodJitter, Self::RiseTime,
+        Self::FallTime, Self::DutyCycle, Self::Mark, Self::Space, Self::CrestFactor, Self::FormFactor
+    ];
+
+    pub fn name(&self) -> &'static str {
+        // also what the measurement is saved as, see format_measurement_list
+        match *self {
+            Self::Vpp => "Vpp",
+            Self::Vrms => "RMS",
+            Self::CycleRms => "Cycle RMS",
+            Self::Mean => "Mean",
+            Self::Frequency => "Frequency",
+            Self::Period => "Period",
+            Self::PeriodJitter => "Jitter",
+            Self::RiseTime => "Rise",
+            Self::FallTime => "Fall",
+            Self::DutyCycle => "Duty",
+            Self::Mark => "Mark",
+            Self::Space => "Space",
+            Self::CrestFactor => "Crest",
+            Self::FormFactor => "Form"
+        }
+    }
+
+    pub fn value(&self, measurements: &MeasurementSet) -> Option<f64> {
+        match *self {
+            Self::Vpp => measurements.vpp,
+            Self::Vrms => measurements.vrms,
+            Self::CycleRms => measurements.cycle_rms,
+            Self::Mean => measurements.mean,
+            Self::Frequency => measurements.frequency,
+            Self::Period => measurements.period,
+            Self::PeriodJitter => measurements.period_jitter,
+            Self::RiseTime => measurements.rise_time,
+            Self::FallTime => measurements.fall_time,
+            Self::DutyCycle => measurements.duty_cycle,
+            Self::Mark => measurements.mark_space.map(|mark_space| mark_space.mark),
+            Self::Space => measurements.mark_space.map(|mark_space| mark_space.space),
+            Self::CrestFactor => measurements.crest_factor,
+            Self::FormFactor => measurements.form_factor
+        }
+    }
+}
+
+pub fn format_measurement_list(measurements: &[Measurement]) -> String {
+    measurements.iter().map(|measurement| measurement.name()).collect::<Vec<&str>>().join(",")
+}
+
+pub fn parse_measurement_list(text: &str) -> Vec<Measurement> {
+    // unknown names are skipped, so an older or hand edited list still loads
+    text.split(',')
+        .filter_map(|name| Measurement::ALL.iter().find(|measurement| measurement.name() == name.trim()).copied())
+        .collect()
+}
+
+pub fn measurements_summary(samples: &[f64], time_per_div: &ValueUnitPair, voltage_per_div: &ValueUnitPair) -> MeasurementSet {
+    measurements_selected(samples, time_per_div, voltage_per_div, &Measurement::ALL, None)
+}
+
+pub fn measurements_selected(samples: &[f64], time_per_div: &ValueUnitPair, voltage_per_div: &ValueUnitPair, selected: &[Measurement], levels: Option<ReferenceLevels>) -> MeasurementSet {
+    // takes the samples as they are stored for drawing (upside down) and measures the actual signal
+    // only the selected measurements are taken, the others stay None
+    // the reference levels are the right way up, like the signal, and replace its extremes for the edge and pulse measurements
+    if samples.len() < MIN_MEASUREMENT_SAMPLES {
+        return MeasurementSet {unit: voltage_per_div.unit_name.clone(), ..Default::default()};
+    }
+    let wanted = |measurements: &[Measurement]| measurements.iter().any(|measurement| selected.contains(measurement));
+    let signal: Vec<f64> = samples.iter().map(|sample| -sample).collect();
+    let sample_rate = sample_rate(time_per_div);
+    let seconds_per_div = if time_per_div.unit_mult > 0.0 {time_per_div.value / time_per_div.unit_mult} else {0.0};
+    let jitter = if wanted(&[Measurement::Period, Measurement::PeriodJitter]) {period_jitter(&signal, seconds_per_div)} else {None};
+    MeasurementSet {
+        vpp: wanted(&[Measurement::Vpp]).then(|| peak_to_peak(&signal)),
+        vrms: wanted(&[Measurement::Vrms]).then(|| rms(&signal)),
+        cycle_rms: wanted(&[Measurement::CycleRms]).then(|| cycle_rms(&signal, sample_rate)),
+        mean: wanted(&[Measurement::Mean]).then(|| mean(&signal)),
+        frequency: if wanted(&[Measurement::Frequency]) {frequency(&signal, sample_rate)} else {None},
+        period: jitter.map(|(period, _)| period),
+        period_jitter: jitter.map(|(_, jitter)| jitter),
+        rise_time: if wanted(&[Measurement::RiseTime]) {edge_time(&signal, sample_rate, true, levels)} else {None},
+        fall_time: if wanted(&[Measurement::FallTime]) {edge_time(&signal, sample_rate, false, levels)} else {None},
+        duty_cycle: if wanted(&[Measurement::DutyCycle]) {duty_cycle(&signal, levels)} else {None},
+        mark_space: if wanted(&[Measurement::Mark, Measurement::Space]) {mark_space(&signal, seconds_per_div, levels)} else {None},
+        crest_factor: wanted(&[Measurement::CrestFactor]).then(|| crest_factor(&signal)),
+        form_factor: wanted(&[Measurement::FormFactor]).then(|| form_factor(&signal)),
+        unit: voltage_per_div.unit_name.clone()
+    }
+}
+
+impl MeasurementSet {
+    pub fn blank_amplitudes(self: &mut Self) {
+        // a clipped capture cuts off the peaks, so anything based on the amplitude is wrong
+        self.vpp = None;
+        self.vrms = None;
+        self.cycle_rms = None;
+        self.mean = None;
+        self.rise_time = None;
+        self.fall_time = None;
+        self.crest_factor = None;
+        self.form_factor = None;
+    }
+}
+
+fn best_lag(a: &[f64], b: &[f64], max_lag: isize, biased: bool) -> isize {
+    // the lag of b against a with the highest cross-correlation, within max_lag in either direction
+    // the biased estimate divides by the full length, which favors small lags over whole periods away
+    let len = a.len().min(b.len());
+    let (mean_a, mean_b) = (mean(&a[..len]), mean(&b[..len]));
+
+    let mut best_lag: isize = 0;
+    let mut best_correlation = f64::MIN;
+    for lag in -max_lag..=max_lag {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for i in 0..len as isize {
+            let j = i + lag;
+            if j < 0 || j >= len as isize {
+                continue;
+            }
+            sum += (a[i as usize] - mean_a) * (b[j as usize] - mean_b);
+            count += 1;
+        }
+        let correlation = if biased {sum / len as f64} else if count > 0 {sum / count as f64} else {continue};
+        if correlation > best_correlation {
+            best_correlation = correlation;
+            best_lag = lag;
+        }
+    }
+    best_lag
+}
+
+pub fn phase_difference_deg(a: &[f64], b: &[f64], sample_rate: f64) -> Option<f64> {
+    // find the lag with the highest cross-correlation and turn it into degrees of the fundamental
+    let frequency = frequency(a, sample_rate)?;
+    let len = a.len().min(b.len());
+    let period_samples = (sample_rate / frequency).round() as isize;
+    if len < 2 || period_samples < 2 {
+        return None;
+    }
+    // half a period in either direction covers every possible phase
+    let lag = best_lag(a, b, period_samples / 2, false);
+    Some(lag as f64 / sample_rate * frequency * 360.0)
+}
+
+pub fn align_to_reference(new: &[f64], ref_: &[f64]) -> isize {
+    // how many samples new is shifted against ref_, searched over a quarter of the capture
+    let len = new.len().min(ref_.len());
+    if len < 2 {
+        return 0;
+    }
+    best_lag(ref_, new, (len / 4) as isize, true)
+}
+
+pub fn slice_on_trigger(samples: &[f64], level: f64, period_samples: usize, holdoff_samples: usize) -> Vec<Vec<f64>> {
+    // cuts the signal into slices of two unit intervals, starting half an interval before every crossing
+    // overlaying the slices puts the crossings at 1/4 and 3/4 of the slice with the eye in the middle
+    // crossings closer than the holdoff to the last used one are skipped
+    let mut slices: Vec<Vec<f64>> = Vec::new();
+    if period_samples < 2 {
+        return slices;
+    }
+    let half_period = period_samples / 2;
+
+    let mut last_crossing: Option<usize> = None;
+    for crossing in find_crossings(samples, level) {
+        if last_crossing.is_some_and(|last| crossing - last < holdoff_samples) {
+            continue;
+        }
+        last_crossing = Some(crossing);
+        if crossing < half_period || crossing - half_period + 2 * period_samples > samples.len() {
+            continue;
+        }
+        let start = crossing - half_period;
+        slices.push(samples[start..start + 2 * period_samples].to_vec());
+    }
+    slices
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IdealShape {
+    Sine,
+    Square,
+    Triangle
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct IdealFit {
+    pub frequency: f64,
+    pub amplitude: f64,
+    pub offset: f64,
+    // phase at the first sample in degrees, every shape rises through its offset at 0°
+    pub phase: f64
+}
+
+pub fn fit_ideal(samples: &[f64], sample_rate: f64) -> Option<IdealFit> {
+    // frequency like the measurement, amplitude and offset from the extremes and the phase from the first rising crossing
+    // the extremes fit a square wave with any duty cycle, the mean would be off for those
+    let frequency = frequency(samples, sample_rate)?;
+    let (min, max) = samples.iter().fold((f64::MAX, f64::MIN), |(min, max), sample| (min.min(*sample), max.max(*sample)));
+    let offset = (min + max) / 2.0;
+    let crossing = find_crossings(samples, offset).into_iter().find(|i| samples[*i] >= offset)?;
+    // interpolate between the two samples around the crossing
+    let (before, after) = (samples[crossing - 1], samples[crossing]);
+    let position = crossing as f64 - 1.0 + if after != before {(offset - before) / (after - before)} else {0.0};
+    let phase = (-360.0 * frequency * position / sample_rate).rem_euclid(360.0);
+    Some(IdealFit{frequency, amplitude: (max - min) / 2.0, offset, phase})
+}
+
+pub fn ideal_waveform(shape: IdealShape, fit: &IdealFit, sample_rate: f64, num_samples: usize) -> Vec<f64> {
+    // the ideal shape sampled like a capture with the given sample rate
+    if sample_rate <= 0.0 {
+        return vec![fit.offset; num_samples];
+    }
+    (0..num_samples).map(|i| {
+        let angle = 2.0 * PI * fit.frequency * i as f64 / sample_rate + fit.phase.to_radians();
+        let value = match shape {
+            IdealShape::Sine => angle.sin(),
+            IdealShape::Square => if angle.rem_euclid(2.0 * PI) < PI {1.0} else {-1.0},
+            IdealShape::Triangle => angle.sin().asin() * 2.0 / PI
+        };
+        fit.offset + fit.amplitude * value
+    }).collect()
+}
+
 pub fn make_rectangle(voltage_per_division:f64, amplitude:f64, time_per_division:f64, period:f64) -> Vec<f64> {
     let mut new_samples: Vec<f64> = Vec::with_capacity(1000);
     for x in 1..1001{
@@ -287,4 +1470,165 @@ pub fn test_filter(num_samples:usize, step: f64, time_per_divison:f64, voltage_p
         new_samples.push(square);
     }
     new_samples
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_offset_binary_ramp() {
+        let ramp: Vec<u8> = (-100..=100).map(|value: i32| (value + 128) as u8).collect();
+        assert_eq!(detect_sample_format(&ramp), SampleFormat::U8Offset128);
+        let expected: Vec<f64> = (-100..=100).map(|value| value as f64).collect();
+        assert_eq!(decode_samples(&ramp, SampleFormat::Auto), expected);
+    }
+
+    #[test]
+    fn detects_signed_ramp() {
+        let ramp: Vec<u8> = (-100..=100).map(|value: i32| value as i8 as u8).collect();
+        assert_eq!(detect_sample_format(&ramp), SampleFormat::I8);
+        let expected: Vec<f64> = (-100..=100).map(|value| value as f64).collect();
+        assert_eq!(decode_samples(&ramp, SampleFormat::Auto), expected);
+    }
+
+    #[test]
+    fn savgol_keeps_parabola() {
+        // a polynomial of the filter's order is fitted exactly, edges included
+        let parabola: Vec<f64> = (0..50).map(|i| {let t = i as f64; 0.5 * t * t - 3.0 * t + 2.0}).collect();
+        let filtered = savgol_filter(&parabola, 7, 2);
+        assert_eq!(filtered.len(), parabola.len());
+        for (filtered, original) in filtered.iter().zip(&parabola) {
+            assert!((filtered - original).abs() < 1E-6, "{filtered} != {original}");
+        }
+    }
+
+    #[test]
+    fn despike_repairs_single_outlier() {
+        // a noisy sine with one wild point, e.g. from a corrupted byte
+        let clean: Vec<f64> = (0..200).map(|i| (i as f64 * 0.1).sin() + if i % 2 == 0 {0.01} else {-0.01}).collect();
+        let mut spiked = clean.clone();
+        spiked[80] += 5.0;
+        let repaired = despike(&spiked, 5.0);
+        let expected = (clean[79] + clean[81]) / 2.0;
+        assert!((repaired[80] - expected).abs() < 1E-12, "{} != {expected}", repaired[80]);
+        // everything else is left exactly as it was
+        for (i, (repaired, clean)) in repaired.iter().zip(&clean).enumerate().filter(|(i, _)| *i != 80) {
+            assert_eq!(repaired, clean, "sample {i} changed");
+        }
+    }
+
+    #[test]
+    fn scale_units_with_other_field_counts() {
+        // the usual layout has the time in the 4th and the voltage in the 8th of 12 fields
+        let usual = String::from("0,DC,0,5ms,1,0,0,2V,0,0,0,0");
+        let fewer = String::from("DC,5ms,2V,0");
+        let more = String::from("0,DC,0,0,0,5ms,1,0,0,0,2V,0,0,0,0,0,0");
+        let reordered = String::from("0,2V,DC,5ms");
+        for condition_string in [usual, fewer, more, reordered] {
+            let (time_per_div, voltage_per_div) = get_scale_units(&condition_string).unwrap();
+            assert_eq!((time_per_div.value, time_per_div.unit_mult), (5.0, 1E3), "{condition_string}");
+            assert_eq!((voltage_per_div.value, voltage_per_div.unit_mult), (2.0, 1.0), "{condition_string}");
+        }
+        assert!(get_scale_units(&String::from("0,DC,0,1,0,0")).is_err());
+    }
+
+    #[test]
+    fn scale_units_prefer_the_usual_fields() {
+        // a trigger level in volts in front of the V/div field must not be taken for the V/div
+        let (time_per_div, voltage_per_div) = get_scale_units(&String::from("0,DC,500mV,5ms,1,0,0,2V,0,0,0,0")).unwrap();
+        assert_eq!((time_per_div.value, time_per_div.unit_mult), (5.0, 1E3));
+        assert_eq!((voltage_per_div.value, voltage_per_div.unit_mult), (2.0, 1.0));
+    }
+
+    #[test]
+    fn exact_nodes_keep_samples() {
+        let samples: Vec<f64> = (0..1000).map(|i| (i as f64 * 0.37).sin() * 40.0 + (i % 7) as f64).collect();
+        for factor in [1, 2, 3, 8] {
+            let interpolated = node_interpolate_samples(&samples, samples.len() * factor);
+            assert_eq!(interpolated.len(), samples.len() * factor);
+            for (i, sample) in samples.iter().enumerate() {
+                assert_eq!(interpolated[i * factor], *sample, "sample {i} at {factor}x");
+            }
+        }
+    }
+
+    #[test]
+    fn linear_and_cosine_cover_full_span() {
+        // no points are dropped at the edges, the trace starts at the first and ends at the last sample
+        let ramp: Vec<f64> = (0..1000).map(|i| i as f64).collect();
+        for num_samples in [1000, 2000, 4000] {
+            for interpolated in [linear_interpolate_samples(&ramp, num_samples, 1.0, 1), cosine_interpolate_samples(&ramp, num_samples, 1.0, 1)] {
+                assert_eq!(interpolated.len(), num_samples);
+                assert_eq!(interpolated[0], ramp[0]);
+                assert!((interpolated[num_samples - 1] - ramp[999]).abs() < 1E-9, "ends at {}", interpolated[num_samples - 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_channel_conditions() {
+        // all fields are there, but none of them has a time or a voltage
+        let empty = String::from("0,DC,0,0,0,0,0,0,0,0,0,0");
+        assert!(is_empty_channel(&empty));
+        assert!(get_scale_units(&empty).is_err());
+        assert!(!is_empty_channel(&String::from("0,DC,0,5ms,1,0,0,2V,0,0,0,0")));
+        // too few fields to be a condition string at all
+        assert!(!is_empty_channel(&String::from("0,DC,0")));
+    }
+
+    fn ramp(len: usize) -> Vec<f64> {
+        (0..len).map(|i| i as f64).collect()
+    }
+
+    #[test]
+    fn interpolation_follows_record_length() {
+        // point i of the drawn trace is at sample position i * len / num_samples, whatever the record length
+        for len in [500, 1000, 4000] {
+            for num_samples in [1001, 2000, 8000] {
+                let interpolated = linear_interpolate_samples(&ramp(len), num_samples, 1.0, 1);
+                assert_eq!(interpolated.len(), num_samples);
+                for (i, value) in interpolated.iter().enumerate() {
+                    let expected = (i as f64 * len as f64 / num_samples as f64).min(len as f64 - 1.0);
+                    assert!((value - expected).abs() < 1E-6, "{len} samples to {num_samples}, point {i}: {value} != {expected}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deep_record_reaches_the_screen() {
+        // the bytes of a deep record through scaling and both passes with the automatic first pass, like a capture
+        for points in [4000, 9999] {
+            let raw: Vec<u8> = (0..points).map(|i| (20 + i * 200 / points) as u8).collect();
+            let samples = scale_waveform_data(&raw, 1.0, 1.0, SampleFormat::U8Offset128);
+            let nodes = fit_sample_count(&samples, auto_first_pass_samples(samples.len()), 1.0, 1);
+            assert_eq!(nodes.len(), 2000);
+            let drawn = linear_interpolate_samples(&nodes, 1001, 1.0, 1);
+            // averaging only pulls the ends in by a block of a few samples, far less than a percent of the ramp
+            let tolerance = (samples[points - 1] - samples[0]).abs() / 100.0;
+            assert!((drawn[0] - samples[0]).abs() <= tolerance, "{points} points start at {}", drawn[0]);
+            assert!((drawn[1000] - samples[points - 1]).abs() <= tolerance, "{points} points end at {} instead of {}", drawn[1000], samples[points - 1]);
+        }
+    }
+
+    #[test]
+    fn two_passes_cover_the_capture() {
+        // the first pass with as many, fewer and more points than samples, then the second pass like reconstruct
+        for len in [500, 1000, 4000] {
+            for first_pass in [len, len / 2, len * 2] {
+                let nodes = fit_sample_count(&ramp(len), first_pass, 1.0, 1);
+                assert_eq!(nodes.len(), first_pass);
+                for interpolated in [linear_interpolate_samples(&nodes, 2000, 1.0, 1), cosine_interpolate_samples(&nodes, 2000, 1.0, 1)] {
+                    let last = interpolated[interpolated.len() - 1];
+                    // averaging blocks of samples pulls the ends in by half a block
+                    let slack = (len as f64 / first_pass as f64).max(len as f64 / 2000.0);
+                    assert!(interpolated[0] <= slack, "{len} samples via {first_pass} start at {}", interpolated[0]);
+                    assert!(last >= len as f64 - 1.0 - slack, "{len} samples via {first_pass} end at {last}");
+                }
+            }
+        }
+        // as many first pass points as samples leaves them alone
+        assert_eq!(fit_sample_count(&ramp(1000), 1000, 1.0, 1), ramp(1000));
+    }
+}
@@ -0,0 +1,94 @@
+// the drawing window draws the grid and the traces this far down, the export does the same so both line up
+const Y_SHIFT: f32 = 5.0;
+const GRID_COLUMNS: usize = 10;
+const GRID_ROWS: usize = 8;
+const FONT_SIZE: f32 = 14.0;
+
+#[derive(Clone, Copy)]
+pub struct SvgView {
+    // size of the drawing window the view was taken from, everything is laid out in its pixels
+    pub width: f32,
+    pub height: f32,
+    // first display sample on screen, the sub-sample shift and the pixels per display sample, like draw_trace_lines
+    pub index_start: usize,
+    pub x_offset: f32,
+    pub x_scale: f32,
+    pub background: [f32; 4],
+    // None leaves the grid out
+    pub grid_opacity: Option<u8>
+}
+
+pub struct SvgTrace<'a> {
+    pub samples: &'a [f64],
+    pub color: [f32; 4],
+    pub thickness: f32,
+    pub y_offset: f32,
+    pub pixels_per_unit: f32
+}
+
+fn rgb(color: [f32; 4]) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("rgb({},{},{})", channel(color[0]), channel(color[1]), channel(color[2]))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn trace_points(trace: &SvgTrace, view: &SvgView) -> Vec<(f32, f32)> {
+    // same mapping as draw_trace_lines and sample_to_y, relative to the window instead of the screen
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    for i in view.index_start..trace.samples.len() {
+        let x = (i - view.index_start) as f32 * view.x_scale + view.x_offset;
+        let y = trace.samples[i] as f32 * trace.pixels_per_unit + Y_SHIFT + view.height / 2.0 + trace.y_offset;
+        points.push((x, y));
+        // one point past the right edge, so the line reaches all the way
+        if x > view.width {
+            break;
+        }
+    }
+    points
+}
+
+pub fn format_view(view: &SvgView, traces: &[SvgTrace], scale_label: &str, annotations: &[String], size: (u32, u32)) -> String {
+    // the view box is the drawing window, the size only scales it, so the output matches the screen at any size
+    let mut text = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" preserveAspectRatio=\"none\">\n", size.0, size.1, view.width, view.height);
+    text.push_str("<defs><clipPath id=\"view\"><rect width=\"100%\" height=\"100%\"/></clipPath></defs>\n");
+    text.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n", view.width, view.height, rgb(view.background)));
+
+    if let Some(opacity) = view.grid_opacity {
+        text.push_str(&format!("<g stroke=\"rgb(244,244,233)\" stroke-opacity=\"{:.3}\" fill=\"none\">\n", opacity as f32 / 255.0));
+        // the center lines are thicker, like draw_grid_lines
+        for i in 1..GRID_COLUMNS {
+            let x = view.width / GRID_COLUMNS as f32 * i as f32;
+            let width = if i == GRID_COLUMNS / 2 {3.0} else {1.0};
+            text.push_str(&format!("<line x1=\"{x:.2}\" y1=\"{Y_SHIFT}\" x2=\"{x:.2}\" y2=\"{:.2}\" stroke-width=\"{width}\"/>\n", view.height + Y_SHIFT));
+        }
+        for i in 1..GRID_ROWS {
+            let y = view.height / GRID_ROWS as f32 * i as f32 + Y_SHIFT;
+            let width = if i == GRID_ROWS / 2 {3.0} else {1.0};
+            text.push_str(&format!("<line x1=\"0\" y1=\"{y:.2}\" x2=\"{}\" y2=\"{y:.2}\" stroke-width=\"{width}\"/>\n", view.width));
+        }
+        text.push_str("</g>\n");
+    }
+
+    text.push_str("<g clip-path=\"url(#view)\" fill=\"none\" stroke-linejoin=\"round\">\n");
+    for trace in traces {
+        let points = trace_points(trace, view);
+        if points.len() < 2 {
+            continue;
+        }
+        let points: Vec<String> = points.iter().map(|(x, y)| format!("{x:.2},{y:.2}")).collect();
+        text.push_str(&format!("<polyline points=\"{}\" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{}\"/>\n", points.join(" "), rgb(trace.color), trace.color[3].clamp(0.0, 1.0), trace.thickness));
+    }
+    text.push_str("</g>\n");
+
+    // the measurements go into the top left corner, the scale into the bottom left one like on the scope's screen
+    text.push_str(&format!("<g font-family=\"monospace\" font-size=\"{FONT_SIZE}\" fill=\"white\">\n"));
+    for (i, annotation) in annotations.iter().enumerate() {
+        text.push_str(&format!("<text x=\"8\" y=\"{:.1}\">{}</text>\n", FONT_SIZE * (i + 1) as f32 + Y_SHIFT, escape(annotation)));
+    }
+    text.push_str(&format!("<text x=\"8\" y=\"{:.1}\">{}</text>\n", view.height - 8.0, escape(scale_label)));
+    text.push_str("</g>\n</svg>\n");
+    text
+}
@@ -1,6 +1,6 @@
 use glium::glutin::surface::WindowSurface;
 use glium::{Display, Surface};
-use imgui::{Context, FontConfig, FontSource, Ui};
+use imgui::{ConfigFlags, Context, FontConfig, FontSource, Ui};
 use imgui_glium_renderer::Renderer;
 use imgui_winit_support::winit::dpi::LogicalSize;
 use imgui_winit_support::winit::event::{Event, WindowEvent};
@@ -25,7 +25,8 @@ where
     FUi: FnMut(&mut bool, &mut Ui) + 'static,
 {
     let mut imgui = create_context();
-    //imgui.io_mut().config_flags |= ConfigFlags::DOCKING_ENABLE;
+    // control windows can be docked into each other once the layout is unlocked
+    imgui.io_mut().config_flags |= ConfigFlags::DOCKING_ENABLE;
     let title = match Path::new(&title).file_name() {
         Some(file_name) => file_name.to_str().unwrap(),
         None => title,
@@ -143,7 +144,8 @@ pub fn create_context() -> imgui::Context {
             }),
         },
     ]);
-    imgui.set_ini_filename(None);
+    // remembers where the control windows were moved to while the layout was unlocked
+    imgui.set_ini_filename(Some(std::path::PathBuf::from("scope-ui.ini")));
 
     imgui
 }